@@ -1,99 +1,1653 @@
+use heck::ToUpperCamelCase;
 use itertools::Itertools;
-use proc_macro2::{Ident, TokenStream};
-use quote::quote;
-use syn::Expr;
+use proc_macro2::{Delimiter, Ident, Literal, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::{
+    spanned::Spanned, Expr, ExprLit, GenericArgument, GenericParam, Generics, Lit, LitStr, Path,
+    PathArguments, Type,
+};
 
-use crate::parser::GenericOptArg;
+use crate::parser::{
+    GenericOptArg, OptArgsItem, OptArgsItemEnum, OptArgsItemFn, OptArgsItemStruct, OptArgsItemType,
+};
 
-pub(crate) fn compute_combinations(opt_args: &[GenericOptArg], shuffle: bool) -> Vec<Vec<&Ident>> {
+pub(crate) fn compute_combinations<'a>(opt_args: &[&'a GenericOptArg]) -> Vec<Vec<&'a Ident>> {
+    // keyword arguments are part of every combination
+    let optional: Vec<_> = opt_args.iter().filter(|arg| !arg.keyword).collect();
     let mut result = vec![];
-    for i in 0..=opt_args.len() {
-        result.extend(if shuffle {
+    for i in 0..=optional.len() {
+        result.extend(optional.iter().combinations(i).map(|combination| {
             opt_args
                 .iter()
-                .permutations(i)
-                .map(|permutation| permutation.iter().map(|a| &a.ident).collect())
-                .collect::<Vec<_>>()
-        } else {
-            opt_args
+                .filter(|arg| arg.keyword || combination.iter().any(|a| a.ident == arg.ident))
+                .map(|a| &a.ident)
+                .collect()
+        }))
+    }
+    result
+}
+
+/// Number of branches generated for the combinations of optional arguments, passing them by name
+/// and, with `positional`, passing from 1 to `positional` of them by position first
+pub(crate) fn count_combinations(opt_args: usize, positional: usize) -> u128 {
+    2u128
+        .saturating_pow(opt_args as u32)
+        .saturating_mul(positional as u128 + 1)
+}
+
+/// All the arguments of an item in the order of declaration,
+/// which differs from required arguments followed by optional arguments when some are skipped
+fn in_order<'a>(
+    required_args: &'a [GenericOptArg],
+    opt_args: &'a [GenericOptArg],
+) -> Vec<&'a GenericOptArg> {
+    required_args
+        .iter()
+        .chain(opt_args)
+        .sorted_by_key(|arg| arg.index)
+        .collect()
+}
+
+/// Everything needed to generate the branches of the macro
+pub(crate) struct MacroInfo<'a> {
+    /// name of the item to call or instantiate
+    pub name: &'a Ident,
+    /// name of the generated macro
+    pub macro_name: &'a Ident,
+    /// path used by the branches of the macro to call it again
+    pub macro_path: TokenStream,
+    /// variant of the enum to instantiate, matched as the first token of each branch
+    pub variant: Option<&'a Ident>,
+    pub required_args: &'a [GenericOptArg],
+    pub opt_args: &'a [GenericOptArg],
+    pub is_function: bool,
+    /// the function is a method, called on the first argument of the macro
+    pub receiver: bool,
+    /// the item is a tuple struct, whose fields can only be passed by position
+    pub tuple: bool,
+    pub shuffle: bool,
+    pub positional: bool,
+    /// path of the module containing the item, used to call it from outside that module
+    pub item_path: Option<TokenStream>,
+    /// name of the helper function, if any optional argument has a deferred default value
+    pub helper: Option<TokenStream>,
+    /// paths imported inside each branch
+    pub prelude: &'a [Path],
+    pub exported: bool,
+    /// bind the passed arguments to variables of the declared type before the call
+    pub transparent_errors: bool,
+    /// bind every argument to a variable named after it before the call
+    pub labeled_calls: bool,
+    /// pass to the function whether each optional argument was provided
+    pub presence_flags: bool,
+    /// minimum number of optional arguments that must be passed
+    pub min_args: usize,
+    /// don't allow passing optional arguments by name after passing some by position
+    pub require_all_named: bool,
+    /// message of the fallback branch, replacing the default one
+    pub error_message: Option<&'a LitStr>,
+    /// deprecation note shown when the macro is called
+    pub deprecated: Option<&'a LitStr>,
+    /// function validating the instantiated struct, whose result is returned by the macro
+    pub validator: Option<TokenStream>,
+    /// ignoring the instantiated struct is reported by the `unused_must_use` lint
+    pub must_use: bool,
+    pub generics: &'a Generics,
+    /// generic parameters of the item alone, passed explicitly when it has const generic optionals
+    pub item_generics: &'a Generics,
+    /// const generic parameters that can be passed by name, after the optional arguments
+    pub const_generics: &'a [GenericOptArg],
+    /// the first argument of the macro is a random number generator,
+    /// used for the default values of the optional arguments
+    pub rng: bool,
+}
+
+pub(crate) fn macro_branches(info: &MacroInfo) -> Vec<TokenStream> {
+    let visible_opt_args: Vec<_> = info
+        .opt_args
+        .iter()
+        .filter(|arg| !arg.is_hidden())
+        .chain(info.const_generics)
+        .collect();
+    // the fields of a tuple struct can only be passed by position, omitting the trailing optionals
+    if info.tuple {
+        let mut result: Vec<_> = (0..=visible_opt_args.len())
+            .map(|k| {
+                let positional: Vec<_> = visible_opt_args[..k]
+                    .iter()
+                    .map(|arg| Some(&arg.ident))
+                    .collect();
+                branch(info, &positional, &[])
+            })
+            .collect();
+        let fallback = fallback(info.error_message, quote!($($tt)*));
+        result.push(quote!(($($tt:tt)*) => {#fallback}));
+        return result;
+    }
+    let mut result: Vec<TokenStream> = vec![];
+    // struct update syntax, where the base supplies the fields that are not passed by name.
+    // At least one field must be passed, since a required field may be a range like `..5`
+    if !info.is_function && info.variant.is_none() {
+        let prefix = info.item_path.as_ref().map(|path| quote!(#path::));
+        let name = info.name;
+        let instance = validated(
+            info,
+            quote!(#prefix #name { $($__opt_args_field: $__opt_args_value,)+ ..$__opt_args_base }),
+        );
+        result.push(quote!(
+            ($($__opt_args_field:ident = $__opt_args_value:expr,)+ .. $__opt_args_base:expr) => {
+                #instance
+            }
+        ));
+    }
+    // internal branches used by `shuffle`, which must come before the others
+    let mut sorting: Vec<TokenStream> = vec![];
+
+    for combination in compute_combinations(&visible_opt_args) {
+        result.push(branch(info, &[], &combination));
+    }
+    // a single optional argument passed by name is always in order
+    if info.shuffle && visible_opt_args.len() > 1 {
+        result.push(sort_entry(info, &[], &visible_opt_args));
+        sorting.extend(sort_branches(info, 0, &visible_opt_args));
+    }
+
+    if info.positional {
+        // the first `k` optionals are passed positionally, and each of them can be skipped with `..`.
+        // Since `..` is also a valid expression, branches with more skips must come first
+        // keyword arguments, optionals marked with `keyword` and const generic parameters
+        // can only be passed by name
+        let candidates: Vec<_> = visible_opt_args
+            [..visible_opt_args.len() - info.const_generics.len()]
+            .iter()
+            .filter(|arg| !arg.keyword && !arg.named_only)
+            .collect();
+        for k in 1..=candidates.len() {
+            let positional: Vec<_> = candidates[..k].iter().copied().copied().collect();
+            let named: Vec<_> = visible_opt_args
                 .iter()
-                .combinations(i)
-                .map(|combination| combination.iter().map(|a| &a.ident).collect())
-                .collect::<Vec<_>>()
+                .filter(|arg| !positional.iter().any(|other| other.ident == arg.ident))
+                .copied()
+                .collect();
+            let masks = (0..k)
+                .map(|_| [true, false])
+                .multi_cartesian_product()
+                .sorted_by_key(|mask| mask.iter().filter(|skip| **skip).count())
+                .rev();
+            let combinations = compute_combinations(&named);
+            for mask in masks {
+                let positional: Vec<_> = positional
+                    .iter()
+                    .zip(mask)
+                    .map(|(arg, skip)| (!skip).then_some(&arg.ident))
+                    .collect();
+                for combination in &combinations {
+                    result.push(branch(info, &positional, combination));
+                }
+                if info.shuffle && named.len() > 1 {
+                    result.push(sort_entry(info, &positional, &named));
+                }
+            }
+            if info.shuffle && named.len() > 1 {
+                sorting.extend(sort_branches(info, k, &named));
+            }
+        }
+    }
+
+    // key-value form for programmatic callers: `f!(required; key = value, ...)`
+    let macro_path = &info.macro_path;
+    let (prefix_pattern, prefix_values) = prefix(info, &[]);
+    let named_args = if prefix_values.is_empty() {
+        quote!($($__opt_args_key = $__opt_args_value),*)
+    } else {
+        quote!($(, $__opt_args_key = $__opt_args_value)*)
+    };
+    result.push(quote!(
+        (#(#prefix_pattern),* ; $($__opt_args_key:ident = $__opt_args_value:expr),* $(,)?) => {
+            #macro_path!(#(#prefix_values),* #named_args)
+        }
+    ));
+
+    // fallback branch for wrong order or wrong names
+    let variant = info.variant;
+    let unmatched = unmatched(info, quote!(#variant $($tt)*), quote!($($tt)*));
+    result.push(quote!((#variant $($tt:tt)*) => {#unmatched}));
+    if checks_names(info) {
+        sorting.extend(typo_branches(info, &visible_opt_args));
+    }
+    sorting.extend(result);
+    sorting
+}
+
+/// The names of the arguments not accepted by the macro are checked
+/// to suggest the closest valid name or to replace aliases
+fn checks_names(info: &MacroInfo) -> bool {
+    info.error_message.is_none() || info.opt_args.iter().any(|arg| arg.alias.is_some())
+}
+
+/// Generate the body of the branches matching arguments that are not accepted by the macro
+fn unmatched(info: &MacroInfo, call: TokenStream, args: TokenStream) -> TokenStream {
+    if checks_names(info) {
+        let macro_path = &info.macro_path;
+        let variant = info.variant;
+        quote!(#macro_path!(@__opt_args_typo #variant [#call] #args))
+    } else {
+        fallback(info.error_message, call)
+    }
+}
+
+/// Generate the internal branches that look for an unknown name among the arguments passed to the macro,
+/// skipping the tokens of each argument up to the next comma.
+/// If an alias is found, the macro is called again replacing every alias with the name of its argument.
+/// An unknown name is reported by the compiler as a missing field of a struct with the valid names,
+/// pointing at the name and suggesting the closest valid one
+fn typo_branches(info: &MacroInfo, opt_args: &[&GenericOptArg]) -> Vec<TokenStream> {
+    let (macro_name, macro_path) = (info.macro_name, &info.macro_path);
+    let variant = info.variant;
+    let typo = quote!(@__opt_args_typo #variant $__opt_args_call:tt);
+    let skip =
+        quote!(#macro_path!(@__opt_args_skip #variant $__opt_args_call $($__opt_args_rest)*));
+    let mut result: Vec<_> = opt_args
+        .iter()
+        .map(|arg| {
+            let ident = &arg.ident;
+            quote!((#typo #ident = $($__opt_args_rest:tt)*) => {#skip})
         })
+        .collect();
+    result.extend(alias_branches(info, opt_args));
+
+    if let Some(message) = info.error_message {
+        // a custom message replaces the suggestion
+        let fallback = fallback(Some(message), quote!($($__opt_args_call)*));
+        result.push(quote!(
+            (@__opt_args_typo #variant [$($__opt_args_call:tt)*] $__opt_args_key:ident = $($__opt_args_rest:tt)*) => {
+                #fallback
+            }
+        ));
+    } else {
+        // the unknown name is accessed as a field of a struct with a field for each valid name,
+        // so that the compiler reports it where it's written and suggests the closest valid name
+        let arguments = format_ident!("{}Arguments", macro_name.to_string().to_upper_camel_case());
+        let names = opt_args.iter().map(|arg| &arg.ident);
+        result.push(quote!(
+            (#typo $__opt_args_key:ident = $($__opt_args_rest:tt)*) => {{
+                #[allow(dead_code)]
+                struct #arguments {
+                    #(#names: ()),*
+                }
+                let _ = |arguments: #arguments| arguments.$__opt_args_key;
+                unreachable!()
+            }}
+        ));
     }
+    result.push(quote!((#typo $($__opt_args_rest:tt)*) => {#skip}));
+
+    // skip the tokens of the current argument
+    let skip_tag = quote!(@__opt_args_skip #variant $__opt_args_call:tt);
+    let next =
+        quote!(#macro_path!(@__opt_args_typo #variant $__opt_args_call $($__opt_args_rest)*));
+    let slots = opt_args.iter().map(|_| quote!(()));
+    result.extend([
+        quote!((#skip_tag , $($__opt_args_rest:tt)*) => {#next}),
+        quote!((#skip_tag ; $($__opt_args_rest:tt)*) => {#next}),
+        quote!((#skip_tag $__opt_args_token:tt $($__opt_args_rest:tt)*) => {#skip}),
+        // every name is valid, so look for a repeated one
+        quote!((@__opt_args_skip #variant [$($__opt_args_call:tt)*]) => {
+            #macro_path!(
+                @__opt_args_repeat #variant [$($__opt_args_call)*] #(#slots)*
+                ; $($__opt_args_call)*
+            )
+        }),
+    ]);
+    result.extend(repeat_branches(info, opt_args));
     result
 }
 
-pub(crate) fn macro_branches(
-    name: &Ident,
-    combinations: Vec<Vec<&Ident>>,
-    opt_args: &[GenericOptArg],
-    required_args: &[GenericOptArg],
-    is_function: bool,
+/// Generate the internal branches that mark the name of each argument passed to the macro in its own slot,
+/// skipping the tokens of each argument up to the next comma, and report a name found twice.
+/// Without repeated names, the usual fallback message is used
+fn repeat_branches(info: &MacroInfo, opt_args: &[&GenericOptArg]) -> Vec<TokenStream> {
+    let macro_path = &info.macro_path;
+    let variant = info.variant;
+    let tag = quote!(@__opt_args_repeat #variant $__opt_args_call:tt);
+    let skip_tag = quote!(@__opt_args_repeat_skip #variant $__opt_args_call:tt);
+    let slots: Vec<_> = opt_args
+        .iter()
+        .map(|arg| format_ident!("__opt_args_slot_{}", arg.ident))
+        .collect();
+    let mut result = vec![];
+    for (i, arg) in opt_args.iter().enumerate() {
+        let ident = &arg.ident;
+        let pattern = slots.iter().enumerate().map(|(j, slot)| {
+            if i == j {
+                quote!(())
+            } else {
+                quote!($#slot:tt)
+            }
+        });
+        let values = slots.iter().enumerate().map(|(j, slot)| {
+            if i == j {
+                quote!((#ident))
+            } else {
+                quote!($#slot)
+            }
+        });
+        result.push(quote!(
+            (#tag #(#pattern)* ; #ident = $($__opt_args_rest:tt)*) => {
+                #macro_path!(@__opt_args_repeat_skip #variant $__opt_args_call #(#values)* ; $($__opt_args_rest)*)
+            }
+        ));
+        let pattern = slots.iter().enumerate().map(|(j, slot)| {
+            if i == j {
+                quote!(($($__opt_args_previous:tt)+))
+            } else {
+                quote!($#slot:tt)
+            }
+        });
+        let duplicate = duplicate(info, ident, quote!($($__opt_args_call)*));
+        result.push(quote!(
+            (
+                @__opt_args_repeat #variant [$($__opt_args_call:tt)*] #(#pattern)*
+                ; #ident = $($__opt_args_rest:tt)*
+            ) => {#duplicate}
+        ));
+    }
+    // each slot is a group, so the slots can't be confused with the `;` that follows them
+    let slots_pattern = quote!($(($($__opt_args_slot:tt)*))*);
+    let next = quote!(#macro_path!(@__opt_args_repeat #variant $__opt_args_call $(($($__opt_args_slot)*))* ; $($__opt_args_rest)*));
+    let skip = quote!(#macro_path!(@__opt_args_repeat_skip #variant $__opt_args_call $(($($__opt_args_slot)*))* ; $($__opt_args_rest)*));
+    let fallback = fallback(info.error_message, quote!($($__opt_args_call)*));
+    // the variant of an enum is not an argument
+    if let Some(variant) = variant {
+        result.push(quote!((#tag #slots_pattern ; #variant $($__opt_args_rest:tt)*) => {#next}));
+    }
+    result.extend([
+        quote!((#tag #slots_pattern ; $($__opt_args_rest:tt)+) => {#skip}),
+        quote!((@__opt_args_repeat #variant [$($__opt_args_call:tt)*] #slots_pattern ;) => {#fallback}),
+        quote!((#skip_tag #slots_pattern ; , $($__opt_args_rest:tt)*) => {#next}),
+        quote!((#skip_tag #slots_pattern ; ; $($__opt_args_rest:tt)*) => {#next}),
+        quote!((#skip_tag #slots_pattern ; $__opt_args_token:tt $($__opt_args_rest:tt)*) => {#skip}),
+        quote!((@__opt_args_repeat_skip #variant [$($__opt_args_call:tt)*] #slots_pattern ;) => {#fallback}),
+    ]);
+    result
+}
+
+/// Generate the body of the branches matching an argument passed more than once
+fn duplicate(info: &MacroInfo, ident: &Ident, args: TokenStream) -> TokenStream {
+    match info.error_message {
+        Some(_) => fallback(info.error_message, args),
+        None => {
+            let message = format!(
+                "Argument `{}` passed more than once to `{}!`: `",
+                ident, info.macro_name
+            );
+            quote!(compile_error!(concat!(#message, stringify!(#args), "`")))
+        }
+    }
+}
+
+/// Generate the internal branches that replace the aliases found in the arguments with the names of their arguments,
+/// copying the tokens of each argument up to the next comma, and call the macro again at the end
+fn alias_branches(info: &MacroInfo, opt_args: &[&GenericOptArg]) -> Vec<TokenStream> {
+    let macro_path = &info.macro_path;
+    let variant = info.variant;
+    let aliased: Vec<_> = opt_args
+        .iter()
+        .filter_map(|arg| Some((arg.alias.as_ref()?, &arg.ident)))
+        .collect();
+    if aliased.is_empty() {
+        return vec![];
+    }
+    let typo = quote!(@__opt_args_typo #variant [$($__opt_args_call:tt)*]);
+    let rename = quote!(@__opt_args_alias #variant [$($__opt_args_done:tt)*]);
+    let copy = quote!(@__opt_args_alias_copy #variant [$($__opt_args_done:tt)*]);
+    let mut result = vec![];
+    for (alias, _) in &aliased {
+        result.push(quote!(
+            (#typo #alias = $($__opt_args_rest:tt)*) => {
+                #macro_path!(@__opt_args_alias #variant [] $($__opt_args_call)*)
+            }
+        ));
+    }
+    for (alias, ident) in &aliased {
+        result.push(quote!(
+            (#rename #alias = $($__opt_args_rest:tt)*) => {
+                #macro_path!(@__opt_args_alias_copy #variant [$($__opt_args_done)* #ident =] $($__opt_args_rest)*)
+            }
+        ));
+    }
+    result.extend([
+        quote!((#rename $($__opt_args_rest:tt)*) => {
+            #macro_path!(@__opt_args_alias_copy #variant [$($__opt_args_done)*] $($__opt_args_rest)*)
+        }),
+        quote!((#copy , $($__opt_args_rest:tt)*) => {
+            #macro_path!(@__opt_args_alias #variant [$($__opt_args_done)* ,] $($__opt_args_rest)*)
+        }),
+        quote!((#copy ; $($__opt_args_rest:tt)*) => {
+            #macro_path!(@__opt_args_alias #variant [$($__opt_args_done)* ;] $($__opt_args_rest)*)
+        }),
+        quote!((#copy $__opt_args_token:tt $($__opt_args_rest:tt)*) => {
+            #macro_path!(@__opt_args_alias_copy #variant [$($__opt_args_done)* $__opt_args_token] $($__opt_args_rest)*)
+        }),
+        quote!((#copy) => {#macro_path!($($__opt_args_done)*)}),
+    ]);
+    result
+}
+
+/// Generate the body of the fallback branch, reporting the given arguments at compile time
+pub(crate) fn fallback(error_message: Option<&LitStr>, args: TokenStream) -> TokenStream {
+    match error_message {
+        Some(message) => quote!(compile_error!(concat!(#message, ": `", stringify!(#args), "`"))),
+        None => quote!(compile_error!(concat!(
+            "Unrecognized order or name for arguments: `",
+            stringify!(#args),
+            "`. If you want to pass named parameters in any order, use the attribute #[opt_args(shuffle)]"
+        ))),
+    }
+}
+
+/// Generate the patterns matching the generator (for test helpers), the variant (for enums),
+/// the required arguments and the optional arguments passed by position,
+/// together with the tokens that pass the matched values to the macro again
+fn prefix(info: &MacroInfo, positional: &[Option<&Ident>]) -> (Vec<TokenStream>, Vec<TokenStream>) {
+    let rng = info
+        .rng
+        .then(|| (quote!($__opt_args_rng:expr), quote!($__opt_args_rng)));
+    let variant = info
+        .variant
+        .map(|variant| (quote!(#variant), quote!(#variant)));
+    let args = info
+        .required_args
+        .iter()
+        .map(|arg| Some(&arg.ident))
+        .chain(positional.iter().copied())
+        .map(|arg| match arg {
+            Some(ident) => (quote!($#ident:expr), quote!($#ident)),
+            None => (quote!(..), quote!(..)),
+        });
+    rng.into_iter().chain(variant).chain(args).unzip()
+}
+
+/// Tokens identifying the internal branches that sort the named optionals of a variant,
+/// when the first `positional` optionals are passed by position
+fn sort_tag(info: &MacroInfo, positional: usize) -> TokenStream {
+    let variant = info.variant;
+    let positional = Literal::usize_unsuffixed(positional);
+    quote!(#variant #positional)
+}
+
+/// Generate the branch matching the optional arguments passed by name in any order (with `shuffle`),
+/// which starts sorting them
+fn sort_entry(
+    info: &MacroInfo,
+    positional: &[Option<&Ident>],
+    named: &[&GenericOptArg],
+) -> TokenStream {
+    let macro_path = &info.macro_path;
+    let tag = sort_tag(info, positional.len());
+    let (pattern, values) = prefix(info, positional);
+    let slots = named.iter().map(|_| quote!(()));
+    quote!(
+        (#(#pattern,)* $($__opt_args_key:ident = $__opt_args_value:expr),+) => {
+            #macro_path!(
+                @__opt_args_sort #tag
+                [#(#values,)* $($__opt_args_key = $__opt_args_value),+]
+                (#(#values),*)
+                #(#slots)*
+                ; $($__opt_args_key = $__opt_args_value),+
+            )
+        }
+    )
+}
+
+/// Generate the internal branches that move each optional argument passed by name to its own slot,
+/// one at a time, and then call the macro again with the optionals in the order of declaration.
+/// This way the number of branches is linear in the number of optionals, instead of generating
+/// a branch for each permutation
+fn sort_branches(
+    info: &MacroInfo,
+    positional: usize,
+    named: &[&GenericOptArg],
 ) -> Vec<TokenStream> {
-    let required_args_formatter = if is_function {
-        |GenericOptArg { ident, .. }: &GenericOptArg| quote!($#ident)
+    let macro_path = &info.macro_path;
+    let tag = sort_tag(info, positional);
+    let slots: Vec<_> = named
+        .iter()
+        .map(|arg| format_ident!("__opt_args_slot_{}", arg.ident))
+        .collect();
+    let mut result: Vec<_> = named
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let ident = &arg.ident;
+            let pattern = slots.iter().enumerate().map(|(j, slot)| {
+                if i == j {
+                    quote!(())
+                } else {
+                    quote!($#slot:tt)
+                }
+            });
+            let values = slots.iter().enumerate().map(|(j, slot)| {
+                if i == j {
+                    quote!(($__opt_args_value))
+                } else {
+                    quote!($#slot)
+                }
+            });
+            quote!(
+                (
+                    @__opt_args_sort #tag $__opt_args_call:tt $__opt_args_prefix:tt #(#pattern)*
+                    ; #ident = $__opt_args_value:expr $(, $($__opt_args_rest:tt)*)?
+                ) => {
+                    #macro_path!(
+                        @__opt_args_sort #tag $__opt_args_call $__opt_args_prefix #(#values)*
+                        ; $($($__opt_args_rest)*)?
+                    )
+                }
+            )
+        })
+        .collect();
+
+    // an argument whose slot is already filled is passed more than once
+    for (i, arg) in named.iter().enumerate() {
+        let ident = &arg.ident;
+        let pattern = slots.iter().enumerate().map(|(j, slot)| {
+            if i == j {
+                quote!(($($__opt_args_previous:tt)+))
+            } else {
+                quote!($#slot:tt)
+            }
+        });
+        let duplicate = duplicate(info, ident, quote!($($__opt_args_call)*));
+        result.push(quote!(
+            (
+                @__opt_args_sort #tag [$($__opt_args_call:tt)*] $__opt_args_prefix:tt #(#pattern)*
+                ; #ident = $($__opt_args_rest:tt)*
+            ) => {#duplicate}
+        ));
+    }
+
+    // every argument is in its slot
+    let idents = named.iter().map(|arg| &arg.ident);
+    let named_args = quote!(#($(, #idents = $#slots)?)*);
+    // without a prefix, the first comma is removed by the `@__opt_args_join` branch
+    let join =
+        !info.rng && info.variant.is_none() && info.required_args.is_empty() && positional == 0;
+    let call = if join {
+        quote!(#macro_path!(@__opt_args_join #named_args))
+    } else {
+        quote!(#macro_path!($($__opt_args_prefix)* #named_args))
+    };
+    result.push(quote!(
+        (@__opt_args_sort #tag $__opt_args_call:tt ($($__opt_args_prefix:tt)*) #(($($#slots:expr)?))* ;) => {
+            #call
+        }
+    ));
+    // unknown or repeated names
+    let unmatched = unmatched(
+        info,
+        quote!($($__opt_args_call)*),
+        quote!($($__opt_args_call)*),
+    );
+    result.push(quote!(
+        (@__opt_args_sort #tag [$($__opt_args_call:tt)*] $($__opt_args_rest:tt)*) => {#unmatched}
+    ));
+    if join {
+        result.push(quote!(
+            (@__opt_args_join $(, $($__opt_args_args:tt)*)?) => {
+                #macro_path!($($($__opt_args_args)*)?)
+            }
+        ));
+    }
+    result
+}
+
+/// Generate a single branch of the macro, where the required arguments are followed by
+/// the optional arguments passed by position (`None` if skipped with `..`)
+/// and then by the ones passed by name
+fn branch(info: &MacroInfo, positional: &[Option<&Ident>], named: &[&Ident]) -> TokenStream {
+    let (prefix_pattern, _) = prefix(info, positional);
+    let named_pattern = named.iter().map(|a| quote!(#a = $#a:expr));
+    let pattern = prefix_pattern.into_iter().chain(named_pattern);
+
+    let provided: Vec<_> = positional.iter().flatten().chain(named).copied().collect();
+    let keyword = info.opt_args.iter().filter(|arg| arg.keyword).count();
+    // the branch is still generated to match the `..` of the positional optionals
+    // and the named optionals, which are also valid expressions, and fails to compile
+    let body = if provided.len() - keyword < info.min_args {
+        let message = format!(
+            "At least {} optional arguments must be passed to `{}!`, found {}",
+            info.min_args,
+            info.macro_name,
+            provided.len() - keyword
+        );
+        quote!(compile_error!(#message))
+    } else if info.require_all_named && !positional.is_empty() {
+        let message = format!(
+            "Optional arguments of `{}!` must be passed by name",
+            info.macro_name,
+        );
+        quote!(compile_error!(#message))
     } else {
-        |GenericOptArg { ident, .. }: &GenericOptArg| quote!(#ident: $#ident)
+        branch_body(info, &provided)
+    };
+    // the macro itself can't be deprecated, so each branch uses a deprecated constant
+    let body = match info.deprecated {
+        Some(note) => quote!({
+            #[deprecated(note = #note)]
+            #[allow(non_upper_case_globals)]
+            const opt_args_deprecated: () = ();
+            let () = opt_args_deprecated;
+            #body
+        }),
+        None => body,
     };
-    let opt_args_formatter = if is_function {
-        |a: &Ident, v: &Expr, c: &Vec<&Ident>| {
-            if c.contains(&a) {
-                quote!($#a)
+    quote!((#(#pattern),*) => {#body})
+}
+
+/// Generate the body of a branch, given the optional arguments passed to the macro
+fn branch_body(info: &MacroInfo, provided: &[&Ident]) -> TokenStream {
+    let call = call(info, provided);
+    if info.prelude.is_empty() && !info.transparent_errors && !info.labeled_calls {
+        return call;
+    }
+    let prelude = info
+        .prelude
+        .iter()
+        .map(|path| macro_path(path, info.exported));
+    let bindings = in_order(info.required_args, info.opt_args)
+        .into_iter()
+        .filter_map(|arg| {
+            let GenericOptArg { ident, ty, .. } = arg;
+            let value = bound_value(info, arg, provided)?;
+            // the type can't be written where the macro is called if it depends on generic parameters
+            // or on names that are only in scope where the item is defined
+            if info.transparent_errors
+                && !uses_generics(ty, info.generics)
+                && is_nameable(ty, info.prelude)
+            {
+                Some(quote!(let #ident: #ty = #value;))
             } else {
-                quote!(#v)
+                Some(quote!(let #ident = #value;))
             }
-        }
+        });
+    quote!({
+        #(
+            #[allow(unused_imports)]
+            use #prelude;
+        )*
+        #(#bindings)*
+        #call
+    })
+}
+
+/// The value bound to a variable named after the argument before the call, if any
+fn bound_value(info: &MacroInfo, arg: &GenericOptArg, provided: &[&Ident]) -> Option<TokenStream> {
+    let GenericOptArg {
+        ident,
+        value,
+        deferred,
+        ..
+    } = arg;
+    if !arg.is_optional() || provided.contains(&ident) {
+        (info.transparent_errors || info.labeled_calls).then(|| quote!($#ident))
+    } else if info.labeled_calls && !deferred {
+        Some(quote!(#value))
     } else {
-        |a: &Ident, v: &Expr, c: &Vec<&Ident>| {
-            if c.contains(&a) {
-                quote!(#a: $#a)
+        None
+    }
+}
+
+/// Generate the call or instantiation of the item, given the optional arguments passed to the macro
+fn call(info: &MacroInfo, provided: &[&Ident]) -> TokenStream {
+    let name = info.name;
+    let args = in_order(info.required_args, info.opt_args)
+        .into_iter()
+        .map(|arg| {
+            let GenericOptArg {
+                ident,
+                value,
+                deferred,
+                ..
+            } = arg;
+            let is_provided = !arg.is_optional() || provided.contains(&ident);
+            let value = if bound_value(info, arg, provided).is_some() {
+                quote!(#ident)
+            } else if is_provided {
+                quote!($#ident)
+            } else if info.helper.is_some() && *deferred {
+                return (ident, quote!(::std::option::Option::None));
+            } else {
+                quote!(#value)
+            };
+            // the helper takes the deferred arguments as `Option`
+            if info.helper.is_some() && *deferred {
+                (ident, quote!(::std::option::Option::Some(#value)))
             } else {
-                quote!(#a: #v)
+                (ident, value)
             }
+        });
+    let flags = info
+        .opt_args
+        .iter()
+        .filter(|arg| info.presence_flags && !arg.is_hidden() && !arg.keyword)
+        .map(|arg| {
+            let flag = provided.contains(&&arg.ident);
+            quote!(#flag)
+        });
+    let prefix = info.item_path.as_ref().map(|path| quote!(#path::));
+    let generic_args = generic_args(info, provided);
+    let call = if info.receiver {
+        // methods are called on the receiver, which is the first argument of the macro
+        let mut args = args.map(|(_, value)| value).chain(flags);
+        let receiver = args.next();
+        let method = info.helper.clone().unwrap_or_else(|| quote!(#name));
+        quote!((#receiver).#method #generic_args (#(#args),*))
+    } else if let Some(helper) = &info.helper {
+        let args = args.map(|(_, value)| value).chain(flags);
+        if info.is_function {
+            quote!(#prefix #helper #generic_args (#(#args),*))
+        } else {
+            quote!(#prefix #name #generic_args :: #helper (#(#args),*))
         }
+    } else if info.is_function || info.tuple {
+        let args = args.map(|(_, value)| value).chain(flags);
+        quote!(#prefix #name #generic_args (#(#args),*))
+    } else {
+        let fields = args.map(|(ident, value)| quote!(#ident: #value));
+        let variant = info.variant.map(|variant| quote!(::#variant));
+        quote!(#prefix #name #generic_args #variant { #(#fields),* })
     };
+    validated(info, call)
+}
 
-    let tmp = required_args
+/// Pass the instance to the validator, if any, then mark the result as `#[must_use]` if required
+fn validated(info: &MacroInfo, instance: TokenStream) -> TokenStream {
+    let instance = match &info.validator {
+        Some(validator) => quote!(#validator(#instance)),
+        None => instance,
+    };
+    if !info.must_use {
+        return instance;
+    }
+    // struct expressions are never reported as unused, while calls of `#[must_use]` functions are
+    let note = format!(
+        "`{}!` creates a value without side effects",
+        info.macro_name
+    );
+    quote!({
+        #[must_use = #note]
+        #[inline(always)]
+        const fn __opt_args_must_use<T>(value: T) -> T {
+            value
+        }
+        __opt_args_must_use(#instance)
+    })
+}
+
+/// Generate the explicit generic arguments of the item when it has const generic optionals,
+/// letting the compiler infer the other parameters
+fn generic_args(info: &MacroInfo, provided: &[&Ident]) -> Option<TokenStream> {
+    if info.const_generics.is_empty() {
+        return None;
+    }
+    let args = info
+        .item_generics
+        .params
         .iter()
-        .map(|GenericOptArg { ident, .. }| quote!($#ident:expr));
-    let required_args_pattern = quote!(#(#tmp),*);
+        .filter_map(|param| match param {
+            GenericParam::Lifetime(_) => None,
+            GenericParam::Type(_) => Some(quote!(_)),
+            GenericParam::Const(param) => {
+                let ident = &param.ident;
+                match info.const_generics.iter().find(|arg| arg.ident == *ident) {
+                    Some(_) if provided.contains(&ident) => Some(quote!({ $#ident })),
+                    Some(GenericOptArg { value, .. }) => Some(quote!({ #value })),
+                    None => Some(quote!(_)),
+                }
+            }
+        });
+    Some(quote!(::<#(#args),*>))
+}
 
-    let tmp = required_args.iter().map(required_args_formatter);
-    let required_args_branch = quote!(#(#tmp),*);
-    let mut result: Vec<TokenStream> = vec![];
+/// Generate a hidden helper that computes the deferred default values inside the scope of the item
+/// (or of a variant of an enum), returning the helper and its name.
+/// The helper of a struct or an enum is an associated function of the type, and so is the helper
+/// of a function, of an empty type with the same name.
+/// The helper of an associated function is declared inside the same `impl` block
+pub(crate) fn helper(
+    opt_args_item: &OptArgsItem,
+    variant: Option<&Ident>,
+    associated: bool,
+    required_args: &[GenericOptArg],
+    opt_args: &[GenericOptArg],
+    presence_flags: bool,
+) -> (TokenStream, TokenStream) {
+    let OptArgsItem {
+        vis,
+        constness,
+        asyncness,
+        unsafety,
+        item,
+        ..
+    } = opt_args_item;
+    let params = in_order(required_args, opt_args).into_iter().map(
+        |GenericOptArg {
+             ident,
+             ty,
+             deferred,
+             ..
+         }| {
+            if *deferred {
+                quote!(#ident: ::std::option::Option<#ty>)
+            } else {
+                quote!(#ident: #ty)
+            }
+        },
+    );
+    let defaults = opt_args.iter().filter(|arg| arg.deferred).map(
+        |GenericOptArg {
+             ident,
+             ty,
+             value,
+             cached,
+             ..
+         }| {
+            // the value of a `cached` argument is computed the first time and cloned by each call
+            let value = match cached {
+                true => quote!({
+                    static VALUE: ::std::sync::OnceLock<#ty> = ::std::sync::OnceLock::new();
+                    ::std::clone::Clone::clone(VALUE.get_or_init(|| #value))
+                }),
+                false => quote!(#value),
+            };
+            quote!(
+                let #ident = match #ident {
+                    ::std::option::Option::Some(#ident) => #ident,
+                    ::std::option::Option::None => #value,
+                };
+            )
+        },
+    );
+    // a `static` can't be initialized by a `const fn`
+    let constness = constness.filter(|_| !opt_args.iter().any(|arg| arg.cached));
+    let flags = presence_flag_idents(opt_args, presence_flags);
+    let params = params.chain(flags.iter().map(|flag| quote!(#flag: bool)));
+    let args = in_order(required_args, opt_args)
+        .into_iter()
+        .map(|arg| &arg.ident)
+        .chain(&flags);
+    match item {
+        OptArgsItemType::ItemFn(OptArgsItemFn {
+            ident,
+            generics,
+            receiver,
+            output,
+            ..
+        }) => {
+            let (impl_generics, _, where_clause) = generics.split_for_impl();
+            let path = associated.then(|| quote!(Self::));
+            // the helper of a method takes the same receiver, in place of the first argument
+            let (params, mut call): (Vec<_>, _) = match receiver {
+                Some(receiver) => {
+                    let params = [quote!(#receiver)].into_iter().chain(params.skip(1));
+                    let args = args.skip(1);
+                    (params.collect(), quote!(self.#ident(#(#args),*)))
+                }
+                None => (params.collect(), quote!(#path #ident(#(#args),*))),
+            };
+            if asyncness.is_some() {
+                call = quote!(#call.await);
+            }
+            if unsafety.is_some() {
+                call = quote!(unsafe { #call });
+            }
+            let helper_ident = match associated {
+                true => format_ident!("__opt_args_{}", ident),
+                false => format_ident!("__opt_args"),
+            };
+            let helper = quote!(
+                #[doc(hidden)]
+                #[allow(non_snake_case, unused_unsafe, clippy::too_many_arguments)]
+                #vis #constness #asyncness #unsafety fn #helper_ident #impl_generics (
+                    #(#params),*
+                ) #output #where_clause {
+                    #(#defaults)*
+                    #call
+                }
+            );
+            if associated {
+                return (helper, quote!(#helper_ident));
+            }
+            // the helper of a free function is an associated function of an empty type named after it,
+            // which lives in another namespace and is imported together with the function and the macro
+            (
+                quote!(
+                    #[doc(hidden)]
+                    #[allow(non_camel_case_types)]
+                    #vis enum #ident {}
 
-    for combination in combinations {
-        let tmp = combination.iter().map(|a| quote!(#a = $#a:expr));
-        let opt_args_pattern = quote!(#(#tmp),*);
-        let tmp = [&required_args_pattern, &opt_args_pattern];
-        let tmp = tmp.iter().filter(|e| !e.is_empty());
-        let pattern = quote!(#(#tmp),*);
-        let tmp = opt_args.iter().map(|GenericOptArg { ident, value, .. }| {
-            opt_args_formatter(ident, value.as_ref().unwrap(), &combination)
-        });
-        let opt_args_branch = quote!(#(#tmp),*);
-        let tmp = [&required_args_branch, &opt_args_branch];
-        let tmp = tmp.iter().filter(|e| !e.is_empty());
-        let branch = quote!(#(#tmp),*);
-        let body = if is_function {
-            quote!(#name (#branch))
+                    impl #ident {
+                        #helper
+                    }
+                ),
+                quote!(#ident::#helper_ident),
+            )
+        }
+        OptArgsItemType::ItemStruct(OptArgsItemStruct {
+            ident,
+            generics,
+            tuple,
+            ..
+        }) => {
+            let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+            let instance = match tuple {
+                true => quote!(Self(#(#args),*)),
+                false => quote!(Self { #(#args),* }),
+            };
+            (
+                quote!(
+                    impl #impl_generics #ident #ty_generics #where_clause {
+                        #[doc(hidden)]
+                        #[allow(clippy::too_many_arguments)]
+                        #vis fn __opt_args(#(#params),*) -> Self {
+                            #(#defaults)*
+                            #instance
+                        }
+                    }
+                ),
+                quote!(__opt_args),
+            )
+        }
+        OptArgsItemType::ItemEnum(OptArgsItemEnum {
+            ident, generics, ..
+        }) => {
+            let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+            let variant = variant.expect("the helper of an enum is generated for a variant");
+            let helper_ident = format_ident!("__opt_args_{}", variant);
+            (
+                quote!(
+                    impl #impl_generics #ident #ty_generics #where_clause {
+                        #[doc(hidden)]
+                        #[allow(non_snake_case, clippy::too_many_arguments)]
+                        #vis fn #helper_ident(#(#params),*) -> Self {
+                            #(#defaults)*
+                            Self::#variant { #(#args),* }
+                        }
+                    }
+                ),
+                quote!(#helper_ident),
+            )
+        }
+        OptArgsItemType::ItemImpl(_) | OptArgsItemType::ItemMod(_) => {
+            unreachable!("the items of `impl` blocks and modules are expanded one at a time")
+        }
+    }
+}
+
+/// Generate a module with free functions to build the arguments of the function step by step:
+/// `new` takes the required arguments, each `with_` function sets an optional argument
+/// and `build` calls the function
+pub(crate) fn builder_fn(
+    opt_args_item: &OptArgsItem,
+    module: &Ident,
+    required_args: &[GenericOptArg],
+    opt_args: &[GenericOptArg],
+    prelude: &[Path],
+    presence_flags: bool,
+) -> syn::Result<TokenStream> {
+    let OptArgsItem {
+        vis,
+        asyncness,
+        unsafety,
+        item,
+        ..
+    } = opt_args_item;
+    let OptArgsItemType::ItemFn(OptArgsItemFn {
+        ident,
+        generics,
+        output,
+        ..
+    }) = item
+    else {
+        return Err(syn::Error::new(
+            item.ident().span(),
+            "`builder_fn` is only supported for functions",
+        ));
+    };
+    // the arguments are stored in the partial, so their type must be nameable
+    if let Some(arg) = required_args
+        .iter()
+        .chain(opt_args)
+        .find(|arg| uses_generics(&arg.ty, &Generics::default()))
+    {
+        return Err(syn::Error::new(
+            arg.ty.span(),
+            "`builder_fn` doesn't support `impl Trait` arguments",
+        ));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    // generic parameters may appear only in the return type
+    let marker_types = generics.params.iter().filter_map(|param| match param {
+        GenericParam::Lifetime(lifetime) => {
+            let lifetime = &lifetime.lifetime;
+            Some(quote!(&#lifetime ()))
+        }
+        GenericParam::Type(ty) => {
+            let ty = &ty.ident;
+            Some(quote!(fn() -> #ty))
+        }
+        GenericParam::Const(_) => None,
+    });
+    let fields = required_args.iter().chain(opt_args).map(|arg| {
+        let (ident, ty) = (&arg.ident, &arg.ty);
+        if arg.is_optional() {
+            quote!(#ident: ::std::option::Option<#ty>)
         } else {
-            quote!(#name { #branch })
-        };
-        result.push(quote!((#pattern) => {#body}));
+            quote!(#ident: #ty)
+        }
+    });
+    let required_params = required_args
+        .iter()
+        .map(|GenericOptArg { ident, ty, .. }| quote!(#ident: #ty));
+    let required_idents = required_args.iter().map(|arg| &arg.ident);
+    let opt_idents = opt_args.iter().map(|arg| &arg.ident);
+    let setters = opt_args.iter().map(|GenericOptArg { ident, ty, .. }| {
+        let setter = format_ident!("with_{}", ident);
+        let doc = format!("Set the optional argument `{}`", ident);
+        quote!(
+            #[doc = #doc]
+            pub fn #setter #impl_generics (
+                partial: Partial #ty_generics,
+                #ident: #ty,
+            ) -> Partial #ty_generics #where_clause {
+                Partial {
+                    #ident: ::std::option::Option::Some(#ident),
+                    ..partial
+                }
+            }
+        )
+    });
+    let defaults = opt_args.iter().map(|GenericOptArg { ident, value, .. }| {
+        quote!(
+            let #ident = match #ident {
+                ::std::option::Option::Some(#ident) => #ident,
+                ::std::option::Option::None => #value,
+            };
+        )
+    });
+    let flags = presence_flag_idents(opt_args, presence_flags);
+    let flag_values = opt_args
+        .iter()
+        .map(|arg| &arg.ident)
+        .zip(&flags)
+        .map(|(ident, flag)| quote!(let #flag = #ident.is_some();));
+    let args = in_order(required_args, opt_args)
+        .into_iter()
+        .map(|arg| &arg.ident)
+        .chain(&flags);
+    let mut call = quote!(super::#ident(#(#args),*));
+    if asyncness.is_some() {
+        call = quote!(#call.await);
     }
+    if unsafety.is_some() {
+        call = quote!(unsafe { #call });
+    }
+    let all_idents = required_args.iter().chain(opt_args).map(|arg| &arg.ident);
+    Ok(quote!(
+        #[allow(non_snake_case, clippy::too_many_arguments)]
+        #vis mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+            #(
+                #[allow(unused_imports)]
+                use #prelude;
+            )*
 
-    // fallback branch for wrong order or wrong names
-    result.push(quote!(
-        ($($tt:tt)*) => {
-            panic!(
-                "Unrecognized order or name for arguments: `{}`.\
-                If you want to pass named parameters in any order, use the attribute #[shuffle]",
-                stringify!($($tt)*)
+            /// The arguments collected so far
+            #[must_use]
+            pub struct Partial #impl_generics {
+                #(#fields,)*
+                __marker: ::std::marker::PhantomData<(#(#marker_types,)*)>,
+            }
+
+            /// Start from the required arguments
+            pub fn new #impl_generics (#(#required_params),*) -> Partial #ty_generics #where_clause {
+                Partial {
+                    #(#required_idents,)*
+                    #(#opt_idents: ::std::option::Option::None,)*
+                    __marker: ::std::marker::PhantomData,
+                }
+            }
+
+            #(#setters)*
+
+            /// Call the function, using the default value of the optional arguments that were not set
+            #[allow(unused_unsafe)]
+            pub #asyncness #unsafety fn build #impl_generics (partial: Partial #ty_generics) #output #where_clause {
+                let Partial { #(#all_idents,)* .. } = partial;
+                #(#flag_values)*
+                #(#defaults)*
+                #call
+            }
+        }
+    ))
+}
+
+/// Generate a struct `{Function}Args` with the optional arguments of a function,
+/// whose `Default` implementation uses their default values,
+/// and a function `{function}_with` taking the required arguments followed by the struct
+pub(crate) fn fn_wrapper(
+    opt_args_item: &OptArgsItem,
+    required_args: &[GenericOptArg],
+    opt_args: &[GenericOptArg],
+) -> syn::Result<TokenStream> {
+    let OptArgsItem {
+        vis,
+        asyncness,
+        unsafety,
+        item,
+        ..
+    } = opt_args_item;
+    let OptArgsItemType::ItemFn(OptArgsItemFn {
+        ident,
+        generics,
+        output,
+        ..
+    }) = item
+    else {
+        return Err(syn::Error::new(
+            item.ident().span(),
+            "`fn_wrapper` is only supported for functions",
+        ));
+    };
+    // keyword arguments have no default value, so they are taken by the wrapper like the required ones
+    let (fields, keyword): (Vec<_>, Vec<_>) = opt_args
+        .iter()
+        .filter(|arg| !arg.is_hidden())
+        .partition(|arg| !arg.keyword);
+    // the struct is not generic, and its default values can't depend on the other arguments
+    if let Some(arg) = fields
+        .iter()
+        .find(|arg| arg.deferred || uses_generics(&arg.ty, generics))
+    {
+        return Err(syn::Error::new(
+            arg.ident.span(),
+            "`fn_wrapper` doesn't support optional arguments depending on generic parameters or other arguments",
+        ));
+    }
+    let args_ident = format_ident!("{}Args", ident.to_string().to_upper_camel_case());
+    let wrapper_ident = format_ident!("{}_with", ident);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let field_idents: Vec<_> = fields.iter().map(|arg| &arg.ident).collect();
+    let field_types = fields.iter().map(|arg| &arg.ty);
+    let field_values = fields.iter().map(|arg| &arg.value);
+    let params = required_args
+        .iter()
+        .chain(keyword.iter().copied())
+        .map(|GenericOptArg { ident, ty, .. }| quote!(#ident: #ty));
+    // skipped arguments always use their default value
+    let hidden = opt_args
+        .iter()
+        .filter(|arg| arg.is_hidden())
+        .map(|GenericOptArg { ident, value, .. }| quote!(let #ident = #value;));
+    let args = in_order(required_args, opt_args)
+        .into_iter()
+        .map(|arg| &arg.ident);
+    let mut call = quote!(#ident(#(#args),*));
+    if asyncness.is_some() {
+        call = quote!(#call.await);
+    }
+    if unsafety.is_some() {
+        call = quote!(unsafe { #call });
+    }
+    let args_doc = format!(
+        "Optional arguments of [`{}`], passed to [`{}`]",
+        ident, wrapper_ident
+    );
+    let wrapper_doc = format!(
+        "Call [`{}`] with the optional arguments collected in [`{}`]",
+        ident, args_ident
+    );
+    Ok(quote!(
+        #[doc = #args_doc]
+        #vis struct #args_ident {
+            #(pub #field_idents: #field_types,)*
+        }
+
+        impl ::std::default::Default for #args_ident {
+            fn default() -> Self {
+                Self {
+                    #(#field_idents: #field_values,)*
+                }
+            }
+        }
+
+        #[doc = #wrapper_doc]
+        #[allow(clippy::too_many_arguments, unused_unsafe)]
+        #vis #asyncness #unsafety fn #wrapper_ident #impl_generics (
+            #(#params,)*
+            args: #args_ident,
+        ) #output #where_clause {
+            let #args_ident { #(#field_idents),* } = args;
+            #(#hidden)*
+            #call
+        }
+    ))
+}
+
+/// Generate a builder for a struct: `Struct::builder` takes the required fields,
+/// each `with_` method of `StructBuilder` sets an optional field and `build` instantiates the struct.
+/// The default values are computed by a hidden function of the struct, where `Self` is in scope
+pub(crate) fn builder_struct(
+    opt_args_item: &OptArgsItem,
+    required_args: &[GenericOptArg],
+    opt_args: &[GenericOptArg],
+) -> syn::Result<TokenStream> {
+    let OptArgsItem { vis, item, .. } = opt_args_item;
+    let OptArgsItemType::ItemStruct(OptArgsItemStruct {
+        ident, generics, ..
+    }) = item
+    else {
+        return Err(syn::Error::new(
+            item.ident().span(),
+            "`builder` is only supported for structs",
+        ));
+    };
+    let builder = format_ident!("{}Builder", ident);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let fields = required_args.iter().chain(opt_args).map(|arg| {
+        let (ident, ty) = (&arg.ident, &arg.ty);
+        if arg.is_optional() {
+            quote!(#ident: ::std::option::Option<#ty>)
+        } else {
+            quote!(#ident: #ty)
+        }
+    });
+    let required_params = required_args
+        .iter()
+        .map(|GenericOptArg { ident, ty, .. }| quote!(#ident: #ty));
+    let required_idents = required_args.iter().map(|arg| &arg.ident);
+    let opt_idents = opt_args.iter().map(|arg| &arg.ident);
+    let setters = opt_args.iter().filter(|arg| !arg.is_hidden()).map(
+        |GenericOptArg { ident, ty, .. }| {
+            let setter = format_ident!("with_{}", ident);
+            let doc = format!("Set the optional field `{}`", ident);
+            quote!(
+                #[doc = #doc]
+                #vis fn #setter(mut self, #ident: #ty) -> Self {
+                    self.#ident = ::std::option::Option::Some(#ident);
+                    self
+                }
             )
+        },
+    );
+    let defaults = opt_args.iter().map(|GenericOptArg { ident, value, .. }| {
+        quote!(
+            let #ident = match #ident {
+                ::std::option::Option::Some(#ident) => #ident,
+                ::std::option::Option::None => #value,
+            };
+        )
+    });
+    let all_idents: Vec<_> = required_args
+        .iter()
+        .chain(opt_args)
+        .map(|arg| &arg.ident)
+        .collect();
+    let builder_doc = format!(
+        "Builder of [`{}`], created with [`{}::builder`]",
+        ident, ident
+    );
+    Ok(quote!(
+        #[doc = #builder_doc]
+        #[must_use]
+        #vis struct #builder #generics #where_clause {
+            #(#fields,)*
         }
-    ));
-    result
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            /// Start building from the required fields
+            #[allow(clippy::too_many_arguments)]
+            #vis fn builder(#(#required_params),*) -> #builder #ty_generics {
+                #builder {
+                    #(#required_idents,)*
+                    #(#opt_idents: ::std::option::Option::None,)*
+                }
+            }
+
+            #[doc(hidden)]
+            #vis fn __opt_args_build(builder: #builder #ty_generics) -> Self {
+                let #builder { #(#all_idents,)* } = builder;
+                #(#defaults)*
+                Self { #(#all_idents),* }
+            }
+        }
+
+        impl #impl_generics #builder #ty_generics #where_clause {
+            #(#setters)*
+
+            /// Instantiate the struct, using the default value of the optional fields that were not set
+            #vis fn build(self) -> #ident #ty_generics {
+                #ident::__opt_args_build(self)
+            }
+        }
+    ))
+}
+
+/// Submit to the `inventory` registry of the given type the name of the item (or variant),
+/// the module containing it and the names of its optional arguments
+pub(crate) fn registration(registry: &Path, name: &str, opt_args: &[GenericOptArg]) -> TokenStream {
+    let optional = opt_args
+        .iter()
+        .filter(|arg| !arg.is_hidden() && !arg.keyword)
+        .map(|arg| arg.ident.to_string());
+    quote!(::inventory::submit! {
+        #registry {
+            name: #name,
+            module: ::std::module_path!(),
+            optional: &[#(#optional),*],
+        }
+    })
+}
+
+/// Add a `#[serde(default)]` attribute to each optional field of a struct, so that deserialization
+/// uses the same default values of the macro. Explicit default values are returned by functions
+/// called `{struct}_default_{field}`, generated next to the struct
+pub(crate) fn serde_defaults(
+    opt_args_item: &mut OptArgsItem,
+    opt_args: &[GenericOptArg],
+) -> syn::Result<TokenStream> {
+    let OptArgsItemType::ItemStruct(item_struct) = &mut opt_args_item.item else {
+        return Err(syn::Error::new(
+            opt_args_item.item.ident().span(),
+            "`serde_defaults` is only supported for structs",
+        ));
+    };
+    let mut functions = vec![];
+    for arg in opt_args {
+        let field = item_struct
+            .fields
+            .iter_mut()
+            .find(|field| field.ident == arg.ident)
+            .unwrap();
+        // `Default::default()` is already the default value used by serde
+        if arg.default {
+            field.attrs.push(syn::parse_quote!(#[serde(default)]));
+            continue;
+        }
+        if arg.deferred || uses_generics(&arg.ty, &item_struct.generics) {
+            return Err(syn::Error::new(
+                arg.ident.span(),
+                "`serde_defaults` doesn't support default values depending on generic parameters or other fields",
+            ));
+        }
+        let GenericOptArg {
+            ident, ty, value, ..
+        } = arg;
+        let function = format_ident!("{}_default_{}", item_struct.ident, ident);
+        let path = function.to_string();
+        field
+            .attrs
+            .push(syn::parse_quote!(#[serde(default = #path)]));
+        functions.push(quote!(
+            #[doc(hidden)]
+            #[allow(non_snake_case)]
+            fn #function() -> #ty {
+                #value
+            }
+        ));
+    }
+    Ok(quote!(#(#functions)*))
+}
+
+/// Names of the `bool` parameters added by `presence_flags`, one for each visible optional argument
+pub(crate) fn presence_flag_idents(opt_args: &[GenericOptArg], presence_flags: bool) -> Vec<Ident> {
+    if !presence_flags {
+        return vec![];
+    }
+    opt_args
+        .iter()
+        .filter(|arg| !arg.is_hidden() && !arg.keyword)
+        .map(|arg| format_ident!("has_{}", arg.ident))
+        .collect()
+}
+
+/// Convert a path starting with `crate` to a path starting with `$crate` if the macro is exported,
+/// so that it refers to the crate where the macro is defined
+pub(crate) fn macro_path(path: &Path, exported: bool) -> TokenStream {
+    match path.segments.first() {
+        Some(first) if exported && path.leading_colon.is_none() && first.ident == "crate" => {
+            let rest = path.segments.iter().skip(1);
+            quote!($crate #(::#rest)*)
+        }
+        _ => quote!(#path),
+    }
+}
+
+/// Check whether the given tokens reference any of the generic parameters of the item
+/// (or `Self` or an `impl Trait`), meaning that they can't be used outside the item itself
+pub(crate) fn uses_generics(tokens: &impl ToTokens, generics: &Generics) -> bool {
+    let mut names: Vec<_> = generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(lifetime) => lifetime.lifetime.ident.clone(),
+            GenericParam::Type(ty) => ty.ident.clone(),
+            GenericParam::Const(constant) => constant.ident.clone(),
+        })
+        .collect();
+    names.push(Ident::new("Self", Span::call_site()));
+    names.push(Ident::new("impl", Span::call_site()));
+    references(tokens.to_token_stream(), &names)
+}
+
+/// Check whether every path in the given type can be resolved wherever the macro is called,
+/// because it is a primitive, part of the standard prelude, imported by the `prelude` attribute
+/// or written with a leading `::`
+pub(crate) fn is_nameable(ty: &Type, prelude: &[Path]) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let path = &path.path;
+            let nameable = path.leading_colon.is_some()
+                || path.segments.first().is_some_and(|first| {
+                    let name = first.ident.to_string();
+                    matches!(
+                        name.as_str(),
+                        "bool"
+                            | "char"
+                            | "str"
+                            | "i8"
+                            | "i16"
+                            | "i32"
+                            | "i64"
+                            | "i128"
+                            | "isize"
+                            | "u8"
+                            | "u16"
+                            | "u32"
+                            | "u64"
+                            | "u128"
+                            | "usize"
+                            | "f32"
+                            | "f64"
+                            | "String"
+                            | "Vec"
+                            | "Option"
+                            | "Result"
+                            | "Box"
+                            | "std"
+                            | "core"
+                            | "alloc"
+                    ) || prelude.iter().any(|import| {
+                        import
+                            .segments
+                            .last()
+                            .is_some_and(|last| last.ident == first.ident)
+                    })
+                });
+            nameable
+                && path
+                    .segments
+                    .iter()
+                    .all(|segment| match &segment.arguments {
+                        PathArguments::None => true,
+                        PathArguments::AngleBracketed(arguments) => {
+                            arguments.args.iter().all(|argument| match argument {
+                                GenericArgument::Lifetime(_) => true,
+                                GenericArgument::Type(ty) => is_nameable(ty, prelude),
+                                _ => false,
+                            })
+                        }
+                        PathArguments::Parenthesized(_) => false,
+                    })
+        }
+        Type::Reference(reference) => is_nameable(&reference.elem, prelude),
+        Type::Ptr(pointer) => is_nameable(&pointer.elem, prelude),
+        Type::Slice(slice) => is_nameable(&slice.elem, prelude),
+        Type::Array(array) => {
+            matches!(array.len, Expr::Lit(_)) && is_nameable(&array.elem, prelude)
+        }
+        Type::Paren(paren) => is_nameable(&paren.elem, prelude),
+        Type::Group(group) => is_nameable(&group.elem, prelude),
+        Type::Tuple(tuple) => tuple.elems.iter().all(|ty| is_nameable(ty, prelude)),
+        Type::Never(_) => true,
+        _ => false,
+    }
+}
+
+/// Render the given tokens for the documentation,
+/// adding spaces only between words and after commas and semicolons
+pub(crate) fn render(tokens: &impl ToTokens) -> String {
+    let mut result = String::new();
+    let mut word = false;
+    for token in tokens.to_token_stream() {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{ ", " }"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                };
+                result += &format!("{}{}{}", open, render(&group.stream()), close);
+                word = false;
+            }
+            TokenTree::Punct(punct) => {
+                result.push(punct.as_char());
+                if matches!(punct.as_char(), ',' | ';') {
+                    result.push(' ');
+                }
+                word = false;
+            }
+            token => {
+                if word {
+                    result.push(' ');
+                }
+                result += &token.to_string();
+                word = true;
+            }
+        }
+    }
+    result.trim_end().to_string()
+}
+
+/// Check whether the given tokens reference any of the given arguments
+pub(crate) fn uses_args(tokens: &impl ToTokens, args: &[GenericOptArg]) -> bool {
+    let names: Vec<_> = args.iter().map(|arg| arg.ident.clone()).collect();
+    references(tokens.to_token_stream(), &names)
+}
+
+/// Check whether the given tokens use `.await`, `?` or other control flow,
+/// which only works inside the body of the function calling the macro
+pub(crate) fn uses_control_flow(tokens: &impl ToTokens) -> bool {
+    fn scan(stream: TokenStream) -> bool {
+        stream.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => ["await", "return", "break", "continue", "yield"]
+                .iter()
+                .any(|keyword| ident == keyword),
+            TokenTree::Punct(punct) => punct.as_char() == '?',
+            TokenTree::Group(group) => scan(group.stream()),
+            _ => false,
+        })
+    }
+    scan(tokens.to_token_stream())
+}
+
+fn references(stream: TokenStream, names: &[Ident]) -> bool {
+    stream.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => names.contains(&ident),
+        TokenTree::Group(group) => references(group.stream(), names),
+        _ => false,
+    })
+}
+
+/// Generate an assertion that fails at compile time if the optional argument
+/// doesn't implement `Default`
+pub(crate) fn assert_default(arg: &GenericOptArg) -> TokenStream {
+    let ty = &arg.ty;
+    quote_spanned!(ty.span()=>
+        const _: fn() = || {
+            fn assert_default<T: ::std::default::Default>() {}
+            assert_default::<#ty>();
+        };
+    )
+}
+
+/// Generate a check that fails at compile time if the default value of the optional argument
+/// doesn't have its type, reporting the error on the default value instead of the calls of the macro
+pub(crate) fn assert_default_type(arg: &GenericOptArg) -> TokenStream {
+    let (ty, value) = (&arg.ty, &arg.value);
+    quote_spanned!(value.span()=>
+        const _: fn() = || {
+            let _: #ty = #value;
+        };
+    )
+}
+
+/// Emit a warning on stable Rust by using a deprecated constant
+pub(crate) fn warning(span: Span, message: &str) -> TokenStream {
+    quote_spanned!(span=>
+        const _: () = {
+            #[deprecated(note = #message)]
+            #[allow(non_upper_case_globals)]
+            const opt_args_warning: () = ();
+            opt_args_warning
+        };
+    )
+}
+
+/// Check whether the given default value is the same as `Default::default()` for a primitive type
+pub(crate) fn is_redundant_default(ty: &Type, value: &Expr) -> bool {
+    let Expr::Lit(ExprLit { lit, .. }) = value else {
+        return false;
+    };
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let Some(ty) = path.path.get_ident().map(Ident::to_string) else {
+                return false;
+            };
+            match (ty.as_str(), lit) {
+                (
+                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+                    | "u128" | "usize",
+                    Lit::Int(int),
+                ) => int.base10_parse::<u128>().is_ok_and(|int| int == 0),
+                ("f32" | "f64", Lit::Float(float)) => {
+                    float.base10_parse::<f64>().is_ok_and(|float| float == 0.0)
+                }
+                ("f32" | "f64", Lit::Int(int)) => {
+                    int.base10_parse::<u128>().is_ok_and(|int| int == 0)
+                }
+                ("bool", Lit::Bool(boolean)) => !boolean.value,
+                ("char", Lit::Char(char)) => char.value() == '\0',
+                _ => false,
+            }
+        }
+        Type::Reference(reference) => {
+            matches!(reference.elem.as_ref(), Type::Path(path) if path.path.is_ident("str"))
+                && matches!(lit, Lit::Str(str) if str.value().is_empty())
+        }
+        _ => false,
+    }
 }
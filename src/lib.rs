@@ -89,6 +89,31 @@
 //! assert_eq!(result, 1 + 5 + 3);
 //! ```
 //!
+//! ## Key-value form
+//! The generated macro also accepts the required arguments followed by a `;` and a list of named arguments.
+//! This form is meant for other macros that forward a list of `key = value` pairs,
+//! since it doesn't need to handle the comma between required and named arguments:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! macro_rules! call_f {
+//!     ($a:expr $(, $key:ident = $value:expr)*) => {
+//!         f!($a; $($key = $value),*)
+//!     };
+//! }
+//!
+//! assert_eq!(call_f!(1), 1 + 5 + 0);
+//! assert_eq!(call_f!(1, b = 2, c = 3), 1 + 2 + 3);
+//! assert_eq!(f!(1;), 1 + 5 + 0);
+//! ```
+//! Named arguments passed this way follow the same rules about the order as the normal form.
+//!
 //! # Options
 //! ## Order of optionals
 //! By default, named arguments must be passed in the same order as they are declared in the item.
@@ -106,6 +131,18 @@
 //! let result = f!(1, c = 3, b = 0);
 //! assert_eq!(result, 1 + 0 + 3);
 //! ```
+//! Passing the same argument more than once results in a compile error naming the repeated argument:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! f!(1, b = 3, b = 4);
+//! ```
 //! This behavior can be changed with the `shuffle` attribute. This attribute allows to call the
 //! function with arbitrary order of named arguments:
 //! ```
@@ -121,13 +158,254 @@
 //! let result = f!(1, c = 3, b = 1);
 //! assert_eq!(result, 1 + 1 + 3);
 //! ```
-//! <span style="color:red">**IMPORTANT**</span>: this doesn't come without disadvantage:
-//! to obtain this result, [`macro@opt_args`] creates a macro that matches any possible
-//! permutation of the given optional arguments. When applying the `shuffle` attribute,
-//! the number of possible permutations scales in the order of `n!`, where `n` is the number of
-//! optional arguments.
-//! While macro expansion has no impact on runtime, it may impact compile time
-//! with a great number of optionals.
+//! To obtain this result, the generated macro sorts the named arguments one at a time
+//! before matching them, so the number of branches doesn't depend on the possible orders
+//! of the optional arguments.
+//! Passing the same argument more than once still results in a compile error:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(shuffle)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! f!(1, c = 3, b = 1, c = 4);
+//! ```
+//!
+//! The macro still has a branch for each combination of optional arguments, which doubles with each of them.
+//! To avoid slowing down the compilation, a `shuffle` macro with more than 5040 branches results in a compile error
+//! stating the number of branches. The limit can be changed with the `max_permutations` attribute,
+//! which also limits the macros without `shuffle`, otherwise unlimited:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(shuffle, max_permutations = 100)]
+//!     fn f(a: u8?, b: u8?, c: u8?, d: u8?, e: u8?, f: u8?, g: u8?, h: u8?) -> u8 {
+//!         a + b + c + d + e + f + g + h
+//!     }
+//! }
+//! ```
+//!
+//! ## Positional optionals
+//! With the `positional` attribute, optional arguments can also be passed by position,
+//! right after the required ones. Use `..` to skip an optional argument and keep its default value.
+//! The optional arguments that are not passed by position can still be passed by name afterwards:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(positional)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?, d: u8 = 1) -> (u8, u8, u8, u8) {
+//!         (a, b, c, d)
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1, 2), (1, 2, 0, 1));
+//! assert_eq!(f!(1, .., 3), (1, 5, 3, 1));
+//! assert_eq!(f!(1, .., 3, d = 4), (1, 5, 3, 4));
+//! assert_eq!(f!(1, c = 3), (1, 5, 3, 1));
+//! ```
+//! Since `..` is also a valid expression, passing `..` by position to an optional argument
+//! always means skipping it. To pass a `RangeFull` value, pass it by name.
+//!
+//! With the `require_all_named` attribute, optional arguments must always be passed by name,
+//! even together with `positional`. In the example above, this would reject `f!(1, 2)`, `f!(1, .., 3)`
+//! and `f!(1, .., 3, d = 4)` with a compile error explaining the requirement:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(positional, require_all_named)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> (u8, u8, u8) {
+//!         (a, b, c)
+//!     }
+//! }
+//!
+//! f!(1, 2);
+//! ```
+//!
+//! The same applies to structs, where the optional fields can be passed in declaration order:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(positional)]
+//!     #[derive(Debug, PartialEq)]
+//!     struct Color {
+//!         r: u8?,
+//!         g: u8?,
+//!         b: u8 = 255,
+//!     }
+//! }
+//!
+//! assert_eq!(Color!(255, 0), Color { r: 255, g: 0, b: 255 });
+//! ```
+//!
+//! ## Skipping arguments
+//! An argument marked with `#[opt_args(skip)]` is always passed explicitly, even if it's declared
+//! as optional. The macro takes it after the other required arguments, in order of declaration,
+//! and the default value is ignored. Skipped arguments can be declared among the optional ones:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = 5, #[opt_args(skip)] scale: u8 = 1, c: u8?) -> u8 {
+//!         (a + b + c) * scale
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1, 2), (1 + 5 + 0) * 2);
+//! assert_eq!(f!(1, 2, c = 3), (1 + 5 + 3) * 2);
+//! ```
+//!
+//! ## Aliases
+//! An optional argument marked with `#[opt_args(alias = name)]` can also be passed with another name,
+//! for example to keep the old name working after renaming it. The function only knows the new name:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, #[opt_args(alias = count)] len: u8 = 5) -> u8 {
+//!         a + len
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1, len = 2), 1 + 2);
+//! assert_eq!(f!(1, count = 2), 1 + 2);
+//! ```
+//!
+//! ## Keyword arguments
+//! An argument without default value marked with `#[opt_args(required)]` can only be passed by name,
+//! and omitting it results in a compile error. It's declared among the optional arguments
+//! and, without `shuffle`, passed in the order of declaration like them:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = 5, #[opt_args(required)] c: u8) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1, c = 2), 1 + 5 + 2);
+//! assert_eq!(f!(1, b = 3, c = 2), 1 + 3 + 2);
+//! ```
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = 5, #[opt_args(required)] c: u8) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! f!(1);
+//! ```
+//!
+//! An optional argument marked with `#[opt_args(keyword)]` can also only be passed by name,
+//! so it can be declared anywhere, even before the required arguments:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(#[opt_args(keyword)] verbose: bool = false, a: u8, b: u8 = 5) -> u8 {
+//!         if verbose {
+//!             println!("{} + {}", a, b);
+//!         }
+//!         a + b
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1), 1 + 5);
+//! assert_eq!(f!(1, verbose = true, b = 2), 1 + 2);
+//! ```
+//!
+//! ## Minimum number of optionals
+//! The `min_args` attribute requires at least a certain number of optional arguments to be passed,
+//! for APIs where relying entirely on the default values is likely a mistake.
+//! Passing fewer optional arguments results in a compile error explaining the requirement:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(min_args = 1)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1, c = 3), 1 + 5 + 3);
+//! ```
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(min_args = 1)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! f!(1);
+//! ```
+//!
+//! ## Custom error message
+//! When the macro is called with arguments that don't match any branch, it results in a compile error
+//! about the order or the names of the arguments. When the name of an argument is unknown,
+//! the error suggests the closest valid name:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! f!(1, bb = 3);
+//! ```
+//! The example above fails to compile with ``no field `bb` on type `FArguments` ``, pointing at `bb`
+//! and suggesting to replace it with `b`.
+//!
+//! The `error_message` attribute replaces these messages, for example to point users to a different API:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(error_message = "use `f_builder` instead")]
+//!     fn f(a: u8, b: u8 = 5) -> u8 {
+//!         a + b
+//!     }
+//! }
+//!
+//! f!(1, c = 3);
+//! ```
+//! The example above fails to compile with ``use `f_builder` instead: `1, c = 3` ``.
+//!
+//! ## Const generic parameters
+//! Const generic parameters that can't be inferred from the arguments can be given a default value
+//! with the `const_generics_defaults` attribute. Their default value is declared in the attribute
+//! instead of the signature, and they are passed to the macro by name after the optional arguments,
+//! never by position. The value of a const generic parameter is passed to the item with explicit generic arguments,
+//! while the other generic parameters are still inferred:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(const_generics_defaults(N = 2))]
+//!     fn zeros<T: Default + Copy, const N: usize>(value: T?) -> [T; N] {
+//!         [value; N]
+//!     }
+//! }
+//!
+//! assert_eq!(zeros!(value = 1u8), [1, 1]);
+//! assert_eq!(zeros!(value = 1u8, N = 3), [1, 1, 1]);
+//! assert_eq!(zeros!(; value = 2u8, N = 1), [2]);
+//! ```
 //!
 //! ## Export the macro
 //! By default, the generated macro is annotated with `#[macro_export]` to make it possible to
@@ -179,6 +457,121 @@
 //! In the above example the function macro `macros::f` is reachable, but the function `macros::f`
 //! is not.
 //!
+//! To call the item through its full path instead, pass the path of the module containing it
+//! to the `item_path` attribute. As for `prelude`, paths starting with `crate` refer to the crate
+//! where the macro is defined, while relative paths (starting with `self` or `super`) are resolved
+//! where the macro is used. The item still needs to be visible where the macro is used,
+//! for example with `pub(crate)`:
+//! ```
+//! mod macros {
+//!     # use opt_args::*;
+//!     #
+//!     opt_args! {
+//!         #[opt_args(item_path = crate::macros)]
+//!         pub(crate) fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!             a + b + c
+//!         }
+//!      }
+//! }
+//! # fn main() {
+//! f!(1);
+//! # }
+//! ```
+//!
+//! For the same reason, exporting the macro of an item with a restricted visibility like `pub(super)`
+//! or `pub(in path)` results in a warning suggesting to use `non_export`.
+//!
+//! When the item is declared inside a function body, the macro (exported or not) can be used
+//! in the rest of that body, after its declaration. An exported macro declared this way
+//! triggers the `non_local_definitions` lint: consider using `non_export` in this case.
+//!
+//! Since the exported macro is generated by another macro, the compiler doesn't allow
+//! to reference it with its path (like `use crate::f;` or `crate::f!`) from the crate where it's defined,
+//! where it can only be used after its declaration or through `#[macro_use]` on the parent module.
+//! Other crates import it by path, as `use your_crate::f;`. Without `item_path`, the arms of the macro call
+//! each other with its name, so it must be imported without renaming it, rather than called with its full path.
+//! With `item_path`, they call each other through a hidden import declared next to the macro,
+//! so the macro can also be renamed or called by path (`your_crate::f!(...)`) from other crates.
+//!
+//! The `crate_export` attribute works around this limitation: the exported macro gets a hidden name,
+//! and is imported with its own name by a `use` declaration next to the item, with the visibility
+//! of the item (or `pub(crate)` if the item is private). The macro can then be imported by path
+//! both from the crate where it's defined and from other crates, following the path of the item
+//! (`use your_crate::module::f;`) instead of the root of the crate:
+//! ```
+//! mod shapes {
+//!     # use opt_args::*;
+//!     #
+//!     opt_args! {
+//!         #[opt_args(crate_export, item_path = crate::shapes)]
+//!         pub fn area(width: u32, height: u32 = 1) -> u32 {
+//!             width * height
+//!         }
+//!     }
+//! }
+//!
+//! use shapes::area;
+//! # fn main() {
+//! assert_eq!(area!(3, height = 2), 6);
+//! # }
+//! ```
+//!
+//! The documentation of the generated macro lists the arguments of the item,
+//! with the type and the default value of the optional ones.
+//!
+//! To list the calls accepted by the macro while developing, use the `snapshot_arms` attribute.
+//! It generates a constant named after the macro in upper snake case followed by `_ARMS`,
+//! with a line for each combination of optional arguments:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(snapshot_arms)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! assert_eq!(F_ARMS, "f!(a)\nf!(a, b = ..)\nf!(a, c = ..)\nf!(a, b = .., c = ..)");
+//! ```
+//!
+//! To export the macro without showing it in the documentation, use the `doc_hidden` attribute,
+//! which adds `#[doc(hidden)]` to the generated macro.
+//!
+//! The `doc_example` attribute adds to the documentation of the macro an example calling it
+//! with placeholder values, once with the required arguments only and once setting the first optional argument.
+//! The example is compiled by the doctests of your crate, so the item must be reachable
+//! from the root of the crate. For `pub fn f(a: u8, b: u8 = 5)` the example is:
+//! ```no_run
+//! # use opt_args::*;
+//! # opt_args! {
+//! #     #[opt_args(doc_example)]
+//! #     pub fn f(a: u8, b: u8 = 5) -> u8 {
+//! #         a + b
+//! #     }
+//! # }
+//! # let a = todo!();
+//! f!(a);
+//! # let b = todo!();
+//! f!(a, b = b);
+//! ```
+//!
+//! To deprecate the generated macro, use the `deprecated` attribute with a note.
+//! Since `#[deprecated]` has no effect on the macro itself, each call of the macro emits the warning:
+//! ```compile_fail
+//! #![deny(deprecated)]
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(deprecated = "use `g!` instead")]
+//!     fn f(a: u8, b: u8 = 5) -> u8 {
+//!         a + b
+//!     }
+//! }
+//!
+//! f!(1);
+//! ```
+//!
 //! ## Rename the macro
 //! It's also possible to give the generated macro a different name than the original item:
 //! ```
@@ -195,55 +588,502 @@
 //! assert_eq!(result, f(1, 5, 0));
 //! ```
 //!
-//! # Recursion
-//! It's also possible to use the generated macro inside the original function:
+//! The `macro_case` attribute converts the name of the macro to `snake` case or `pascal` case,
+//! for example to give a function used as a constructor a macro named like the type it builds.
+//! The default is `keep`, which leaves the name unchanged. An explicit `rename` is always used as it is:
 //! ```
 //! # use opt_args::*;
 //! #
 //! opt_args! {
-//!     fn f(a: u8 = 10, b: u8 = 1, c: u8 = 2) -> u8 {
-//!         if a == 0 {
-//!             b + c
-//!         } else {
-//!             f!(a = a - 1, c = c + 1)
-//!         }
+//!     #[opt_args(macro_case = pascal)]
+//!     fn new_point(x: i32?, y: i32?) -> (i32, i32) {
+//!         (x, y)
 //!     }
 //! }
 //!
-//! let result = f!();
-//! assert_eq!(result, 13);
+//! assert_eq!(NewPoint!(y = 1), (0, 1));
 //! ```
 //!
-//! # Generics and lifetimes
-//! The macro supports any kind of generic types, lifetimes and type inference:
+//! The `prefix` and `suffix` attributes add a fixed text before and after the name of the item,
+//! and can be combined. They are applied before `macro_case`, and ignored when `rename` is given:
 //! ```
 //! # use opt_args::*;
 //! #
 //! opt_args! {
-//!     fn generics<'a, 'b, 'c, T: 'c>(
-//!         a: i32?,
-//!         b: &'a str = "default",
-//!         c: (u128, f32)?,
-//!         d: Option<[String; 4]>?,
-//!         e: &'b str?,
-//!         f: Vec<T>?,
-//!     ) -> (i32, &'a str, (u128, f32), Option<[String; 4]>, &'b str, Vec<T>) {
-//!         (a, b, c, d, e, f)
+//!     #[opt_args(prefix = "make_", suffix = "_with")]
+//!     fn point(x: i32?, y: i32?) -> (i32, i32) {
+//!         (x, y)
 //!     }
 //! }
 //!
-//! assert_eq!(
-//!     generics!(b = "!default"),
-//!     (0, "!default", (0, 0.0), None, "", Vec::<u8>::new())
-//! );
-//! assert_eq!(
-//!     generics!(e = "e", f = vec![9]),
-//!     (0, "default", (0, 0.0), None, "e", vec![9])
-//! );
+//! assert_eq!(make_point_with!(x = 1), (1, 0));
 //! ```
 //!
-//! # Types that don't implement Default
-//! It's possible to use the macro to mark as optional even a type that doesn't implement `Default`.
+//! ## Multiple items
+//! More items can be wrapped inside a single invocation, each one generating its own macro.
+//! Block-level options can be given at the beginning of the body with `#![opt_args(...)]`.
+//! The `rename_all_macros` option applies a renaming rule to every generated macro,
+//! replacing `{}` with the name of the item. An explicit `rename` on a single item still takes precedence:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #![opt_args(rename_all_macros = "make_{}")]
+//!
+//!     fn f(a: u8, b: u8 = 5) -> u8 {
+//!         a + b
+//!     }
+//!
+//!     #[opt_args(rename = g_macro)]
+//!     fn g(a: u8?) -> u8 {
+//!         a
+//!     }
+//! }
+//!
+//! assert_eq!(make_f!(1), 6);
+//! assert_eq!(g_macro!(a = 1), 1);
+//! ```
+//! Two items generating a macro with the same name result in a compile error:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(non_export)]
+//!     fn f(a: u8?) -> u8 {
+//!         a
+//!     }
+//!
+//!     #[opt_args(non_export, rename = f)]
+//!     fn g(a: u8?) -> u8 {
+//!         a
+//!     }
+//! }
+//! ```
+//!
+//! ## Existing items
+//! An item marked with `@existing` is already defined outside of the invocation, so it's not emitted again
+//! and only its macro is generated. The declaration must match the existing one,
+//! with the default values added to the optional arguments:
+//! ```
+//! # use opt_args::*;
+//! #
+//! #[derive(Debug, PartialEq)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! opt_args! {
+//!     @existing struct Point {
+//!         x: i32,
+//!         y: i32 = 1,
+//!     }
+//! }
+//!
+//! assert_eq!(Point!(4), Point { x: 4, y: 1 });
+//! ```
+//! Options modifying the item, like `serde_defaults` and `presence_flags`, can't be used for existing items.
+
+//! ## Imports for default values
+//! Default values are evaluated where the macro is called, so any item they reference must be in scope there.
+//! The `prelude` attribute lists paths that are imported inside the generated macro,
+//! making the default values independent of the imports at the call site.
+//! Paths starting with `crate` refer to the crate where the macro is defined:
+//! ```
+//! #[macro_use]
+//! mod defaults {
+//!     # use opt_args::*;
+//!     use std::collections::HashMap;
+//!
+//!     opt_args! {
+//!         #[opt_args(prelude(std::collections::HashMap))]
+//!         pub fn f(map: HashMap<u8, u8> = HashMap::from([(1, 2)])) -> usize {
+//!             map.len()
+//!         }
+//!     }
+//! }
+//! use defaults::f;
+//!
+//! assert_eq!(f!(), 1);
+//! ```
+//!
+//! ## Type errors
+//! The values passed to the macro are placed directly in the call, so a value of the wrong type
+//! may produce an error pointing inside the generated macro.
+//! The `transparent_errors` attribute binds each passed value to a variable with the declared type
+//! before the call, so that the error points at the value and mentions the type of the argument:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(transparent_errors)]
+//!     fn f(a: u8, b: u8 = 5) -> u8 {
+//!         a + b
+//!     }
+//! }
+//!
+//! f!(1, b = "5");
+//! ```
+//! The example above results in the error ```mismatched types: expected `u8`, found `&str` ```.
+//! The type is not written when it depends on generic parameters, or when it uses names that may not be in scope
+//! where the macro is called (anything other than primitives, types of the standard prelude,
+//! paths starting with `::` and names imported with the `prelude` attribute).
+//!
+//! ## Labeled calls
+//! To make the generated code easier to read (for example with `cargo expand`),
+//! the `labeled_calls` attribute binds every argument, including the default values,
+//! to a variable named after it before the call. With this attribute `f!(1, c = 3)` expands to:
+//! ```
+//! # fn f(a: u8, b: u8, c: u8) -> u8 { a + b + c }
+//! # assert_eq!(
+//! {
+//!     let a = 1;
+//!     let b = 5;
+//!     let c = 3;
+//!     f(a, b, c)
+//! }
+//! # , 9);
+//! ```
+//!
+//! ## Builder functions
+//! With `call_convention = builder_fn`, instead of the macro a module with the same name as the function
+//! is generated, containing free functions to collect the arguments step by step:
+//! `new` takes the required arguments, each `with_` function sets an optional argument
+//! and `build` calls the function, using the default value of the optional arguments that were not set:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(call_convention = builder_fn)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let partial = f::new(1);
+//! let partial = f::with_c(partial, 3);
+//! assert_eq!(f::build(partial), 1 + 5 + 3);
+//! # }
+//! ```
+//! The module can be renamed like the macro. Since the functions refer to the original one through `super`,
+//! the function must be declared directly inside a module and not inside a block.
+//! Arguments of type `impl Trait` are not supported.
+//!
+//! ## Wrapper functions
+//! To call a function without the macro, the `fn_wrapper` attribute also generates a struct
+//! named after the function in upper camel case followed by `Args`, with a public field
+//! for each optional argument and a `Default` implementation using their default values,
+//! and a function named after the original one followed by `_with`,
+//! taking the required arguments and the struct:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(fn_wrapper)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(f_with(1, FArgs { c: 3, ..Default::default() }), f!(1, c = 3));
+//! # }
+//! ```
+//! Arguments marked with `required` are taken by the wrapper after the required ones.
+//! Since the struct is not generic, the type and the default value of the optional arguments
+//! can't depend on the generic parameters or on the other arguments.
+//!
+//! ## Named default values
+//! The block-level option `defaults` declares default values that can be shared by more arguments,
+//! even of different items, referencing them with `@` followed by their name:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #![opt_args(defaults(origin = (0, 0)))]
+//!
+//!     fn segment(from: (i32, i32) = @origin, to: (i32, i32) = @origin) -> i32 {
+//!         (to.0 - from.0).abs() + (to.1 - from.1).abs()
+//!     }
+//!
+//!     fn distance(point: (i32, i32), from: (i32, i32) = @origin) -> i32 {
+//!         segment(from, point)
+//!     }
+//! }
+//!
+//! assert_eq!(segment!(to = (1, 2)), 3);
+//! assert_eq!(distance!((3, 4)), 7);
+//! ```
+//! The expression is copied in place of each reference, so it's evaluated every time it's used.
+//!
+//! ## Evaluation of default values
+//! The default value of an argument is evaluated by each call of the macro that omits it.
+//! This is also stated explicitly by marking the argument with `#[opt_args(lazy)]`.
+//! To evaluate it only once instead, mark the argument with `#[opt_args(cached)]`: the value is computed
+//! by the first call omitting the argument, stored in a `static` of the hidden helper generated next to the item
+//! (the same that computes the default values depending on generic parameters), and cloned by each call.
+//! The type must be `Clone`, `Send` and `Sync`, and the value can't depend on the generic parameters
+//! or on the other arguments:
+//! ```
+//! # use opt_args::*;
+//! # use std::sync::atomic::{AtomicUsize, Ordering};
+//! #
+//! static NEXT: AtomicUsize = AtomicUsize::new(0);
+//!
+//! fn next_id() -> usize {
+//!     NEXT.fetch_add(1, Ordering::SeqCst)
+//! }
+//!
+//! opt_args! {
+//!     fn f(#[opt_args(lazy)] a: usize = next_id(), #[opt_args(cached)] b: usize = next_id()) -> (usize, usize) {
+//!         (a, b)
+//!     }
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(f!(), (0, 1));
+//! assert_eq!(f!(), (2, 1));
+//! # }
+//! ```
+//!
+//! ## Fallible default values
+//! With the `try` attribute, the default values are fallible: each one evaluates to a `Result` (or an `Option`)
+//! that is unwrapped with `?` by the calls omitting the argument, so the macro can only be used
+//! inside functions returning a compatible type. Arguments without a default value (`a: T?`) are not affected,
+//! and the default values can't depend on the generic parameters or on the other arguments:
+//! ```
+//! # use opt_args::*;
+//! # use std::num::ParseIntError;
+//! #
+//! opt_args! {
+//!     #[opt_args(try)]
+//!     fn f(a: i32 = "1".parse::<i32>(), b: i32 = "x".parse::<i32>()) -> i32 {
+//!         a + b
+//!     }
+//! }
+//!
+//! fn g() -> Result<i32, ParseIntError> {
+//!     Ok(f!(b = 2))
+//! }
+//!
+//! fn h() -> Result<i32, ParseIntError> {
+//!     Ok(f!(a = 2))
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(g(), Ok(3));
+//! assert!(h().is_err());
+//! # }
+//! ```
+//! `try` can't be used together with `builder`, `call_convention`, `fn_wrapper` and `serde_defaults`,
+//! which evaluate the default values outside of the macro.
+//!
+//! ## Presence flags
+//! To know whether an optional argument was passed or its default value was used,
+//! the `presence_flags` attribute appends to the signature of the function a `bool` parameter
+//! for each optional argument, in the same order, named `has_` followed by the name of the argument:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(presence_flags)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> String {
+//!         format!("{} {} {}", a, if has_b { "b" } else { "-" }, if has_c { "c" } else { "-" })
+//!     }
+//! }
+//!
+//! assert_eq!(f!(1, c = 5), "1 - c");
+//! assert_eq!(f(1, 5, 0, true, false), "1 b -");
+//! ```
+//! The function is rewritten, so calling it directly requires passing the flags too.
+//! This attribute is only supported for functions.
+//!
+//! ## Test helpers
+//! The `test_helpers` attribute generates a second macro, only under `cfg(test)`, named after
+//! the first one followed by `_test`. Its first argument is a random number generator, used
+//! in place of the default values of the optional arguments that are not passed:
+//! each of them is generated by calling `rng.r#gen()`, so any value with a `gen` method generic
+//! over its return type can be used. The method is called by name, so a trait providing it,
+//! like the [`rand::Rng`](https://docs.rs/rand/0.8/rand/trait.Rng.html) of `rand` 0.8,
+//! must be in scope where the macro is called. `rand` 0.9 renamed the method to `random`,
+//! so its generators need a small wrapper, like the local generator below:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(test_helpers)]
+//!     struct Point {
+//!         x: i32 = 0,
+//!         y: i32 = 0,
+//!     }
+//! }
+//!
+//! // generates the values 1, 2, 3, ...
+//! struct Sequence(u8);
+//!
+//! impl Sequence {
+//!     fn r#gen<T: From<u8>>(&mut self) -> T {
+//!         self.0 += 1;
+//!         T::from(self.0)
+//!     }
+//! }
+//!
+//! // like the macro, only compiled in tests
+//! #[cfg(test)]
+//! fn first_point() {
+//!     let mut rng = Sequence(0);
+//!     let point = Point_test!(rng, x = 5);
+//!     assert_eq!((point.x, point.y), (5, 1));
+//! }
+//! ```
+//!
+//! ## Registry
+//! The `register` attribute submits the item to an [`inventory`](https://docs.rs/inventory) registry,
+//! to list at runtime the items with optional arguments. The registry is a type of your crate
+//! with the fields `name`, `module` and `optional`, collected with `inventory::collect!`.
+//! A variant of an enum is registered as `Enum::Variant`, and an associated function as `Type::function`.
+//! The generated code uses `::inventory::submit!`, so the crate must depend on `inventory` itself:
+//! ```
+//! # use opt_args::*;
+//! #
+//! pub struct Registry {
+//!     pub name: &'static str,
+//!     pub module: &'static str,
+//!     pub optional: &'static [&'static str],
+//! }
+//!
+//! inventory::collect!(Registry);
+//!
+//! opt_args! {
+//!     #[opt_args(register = Registry)]
+//!     fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+//!         a + b + c
+//!     }
+//! }
+//!
+//! let f = inventory::iter::<Registry>.into_iter().find(|item| item.name == "f").unwrap();
+//! assert_eq!(f.optional, ["b", "c"]);
+//! ```
+//!
+//! # Recursion
+//! It's also possible to use the generated macro inside the original function:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8 = 10, b: u8 = 1, c: u8 = 2) -> u8 {
+//!         if a == 0 {
+//!             b + c
+//!         } else {
+//!             f!(a = a - 1, c = c + 1)
+//!         }
+//!     }
+//! }
+//!
+//! let result = f!();
+//! assert_eq!(result, 13);
+//! ```
+//!
+//! # Generics and lifetimes
+//! The macro supports any kind of generic types, lifetimes and type inference:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn generics<'a, 'b, 'c, T: 'c>(
+//!         a: i32?,
+//!         b: &'a str = "default",
+//!         c: (u128, f32)?,
+//!         d: Option<[String; 4]>?,
+//!         e: &'b str?,
+//!         f: Vec<T>?,
+//!     ) -> (i32, &'a str, (u128, f32), Option<[String; 4]>, &'b str, Vec<T>) {
+//!         (a, b, c, d, e, f)
+//!     }
+//! }
+//!
+//! assert_eq!(
+//!     generics!(b = "!default"),
+//!     (0, "!default", (0, 0.0), None, "", Vec::<u8>::new())
+//! );
+//! assert_eq!(
+//!     generics!(e = "e", f = vec![9]),
+//!     (0, "default", (0, 0.0), None, "e", vec![9])
+//! );
+//! ```
+//! Lifetimes are inferred in the same way: when an optional argument with a lifetime is omitted,
+//! the lifetime is inferred from its default value (`'static` for a literal like `""`).
+//!
+//! Since it's not possible to use the turbofish syntax on a macro, generic parameters
+//! (including const generics) are always inferred, either from the passed arguments or from the expected type:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn padded<const N: usize>(pad: [u8; N] = [0; N]) -> [u8; N] {
+//!         pad
+//!     }
+//! }
+//!
+//! let result: [u8; 3] = padded!();
+//! assert_eq!(result, [0, 0, 0]);
+//! assert_eq!(padded!(pad = [1, 2]), [1, 2]);
+//! ```
+//! Default values that reference generic parameters (or `Self`) can't be evaluated where the macro is called,
+//! so they are computed by a hidden helper generated next to the item: an associated function `__opt_args`
+//! of the struct, or of an empty type named after the function. Since the type has the same name
+//! as the function, importing the function also imports the helper.
+//!
+//! # Default values depending on other arguments
+//! A default value can reference the arguments declared before it. Such default values are computed
+//! by the same hidden helper, where the other arguments are available:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[derive(Debug, PartialEq)]
+//!     struct Pair<T: Clone> {
+//!         first: T,
+//!         second: T = first.clone(),
+//!     }
+//! }
+//!
+//! assert_eq!(Pair!(5u8), Pair { first: 5, second: 5 });
+//! assert_eq!(Pair!(5u8, second = 6), Pair { first: 5, second: 6 });
+//! ```
+//! The requirements of the default value are checked on the item itself,
+//! so the example above fails to compile without the `Clone` bound on `T`:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     struct Pair<T> {
+//!         first: T,
+//!         second: T = first.clone(),
+//!     }
+//! }
+//! ```
+//!
+//! # Redundant default values
+//! With the `suggest_default` attribute, a warning is emitted for each explicit default value
+//! of a primitive type that is the same as its `Default::default()`, suggesting to use `?` instead:
+//! ```compile_fail
+//! #![deny(deprecated)]
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(suggest_default)]
+//!     fn f(a: u32 = 0, b: bool = false) -> (u32, bool) {
+//!         (a, b)
+//!     }
+//! }
+//! ```
+//! The example above emits the warnings ``the default value of `a` is the same as `Default::default()`,
+//! consider using `a: u32?` `` and the same for `b` (turned into errors by `#![deny(deprecated)]`).
+//!
+//! # Types that don't implement Default
+//! It's possible to use the macro to mark as optional even a type that doesn't implement `Default`.
 //! ```
 //! # use opt_args::*;
 //! #
@@ -257,70 +1097,405 @@
 //!     }
 //! }
 //! ```
-//! In this case it's impossible to call the generated macro `f!` without passing `b` as a named argument,
-//! because it would result in an attempt to use `Default::default()` as value for `X`.
+//! In this case it's impossible to call the generated macro `f!` without passing `b` as a named argument,
+//! because it would result in an attempt to use `Default::default()` as value for `X`.
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! # // doesn't implement `Default`
+//! # struct X {
+//! #     x: usize
+//! # }
+//! #
+//! # opt_args! {
+//! #     fn f(a: X = X { x: 0 }, b: X?) -> usize {
+//! #         a.x + b.x
+//! #     }
+//! # }
+//! f!();
+//! ```
+//! This would result in a call to `f(X { x: 0 }, Default::default())` which would trigger the compile error:
+//! ```the trait `Default` is not implemented for `X` ```.
+//!
+//! This may be useful to force the caller to pass the argument `b` as a named argument.
+//!
+//! To catch the missing implementation where the item is defined, instead of where the macro is called,
+//! use the `assert_defaults` attribute. It generates an assertion for each optional argument without
+//! an explicit default value, skipping the ones whose type depends on generic parameters:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! # // doesn't implement `Default`
+//! # struct X {
+//! #     x: usize
+//! # }
+//! #
+//! opt_args! {
+//!     #[opt_args(assert_defaults)]
+//!     fn f(a: X = X { x: 0 }, b: X?) -> usize {
+//!         a.x + b.x
+//!     }
+//! }
+//! ```
+//! This results in the error ```the trait bound `X: Default` is not satisfied``` pointing at the type of `b`.
+//!
+//! Explicit default values are always checked against the type of their argument where the item is defined,
+//! unless the type depends on generic parameters or the value uses `.await`, `?`, `return`, `break` or `continue`,
+//! which only work where the macro is called:
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     fn f(a: u8, b: u8 = "oops") -> u8 {
+//!         a + b
+//!     }
+//! }
+//! ```
+//! This results in the error ```mismatched types``` pointing at `"oops"`, even if the macro is never called.
+//!
+//! # `PhantomData`
+//! Optional arguments of type `PhantomData` are always set to their default value and
+//! can't be passed to the generated macro:
+//! ```
+//! # use opt_args::*;
+//! # use std::marker::PhantomData;
+//! #
+//! opt_args! {
+//!     #[derive(Debug, PartialEq)]
+//!     struct Id<T> {
+//!         id: u32,
+//!         _marker: PhantomData<T>?,
+//!     }
+//! }
+//!
+//! let id: Id<String> = Id!(1);
+//! assert_eq!(id, Id { id: 1, _marker: PhantomData });
+//! ```
+//!
+//! # Methods
+//! Associated functions are supported by wrapping the whole
+//! (inherent) `impl` block. A macro is generated outside the block for each function with optional
+//! arguments, named after the type and the function, while the other items are left unchanged:
+//! ```
+//! # use opt_args::*;
+//! #
+//! #[derive(Debug, PartialEq)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! opt_args! {
+//!     impl Point {
+//!         fn new(x: i32, y: i32 = 0) -> Self {
+//!             Self { x, y }
+//!         }
+//!
+//!         fn x(&self) -> i32 {
+//!             self.x
+//!         }
+//!     }
+//! }
+//!
+//! assert_eq!(Point_new!(1, y = 2), Point { x: 1, y: 2 });
+//! assert_eq!(Point_new!(1).x(), 1);
+//! ```
+//! Options are applied to each function, and `builder_fn` and `fn_wrapper` are not supported.
+//!
+//! The receiver of a method, like `&self` or `self: Box<Self>`, is the first argument of the macro,
+//! and the method is called on it:
+//! ```
+//! # use opt_args::*;
+//! #
+//! struct Counter {
+//!     count: u8,
+//! }
+//!
+//! opt_args! {
+//!     impl Counter {
+//!         fn get(&self, offset: u8 = 0) -> u8 {
+//!             self.count + offset
+//!         }
+//!
+//!         fn consume(self: Box<Self>, offset: u8 = 1) -> u8 {
+//!             self.count + offset
+//!         }
+//!     }
+//! }
+//!
+//! let counter = Counter { count: 1 };
+//! assert_eq!(Counter_get!(counter, offset = 2), 3);
+//! assert_eq!(Counter_consume!(Box::new(counter)), 2);
+//! ```
+//!
+//! # Structs
+//! The syntax and usage of the macro for structs is the same as it is for functions:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(shuffle)]
+//!     #[derive(Debug, PartialEq, Eq)]
+//!     struct Opt<'a, 'b, T: 'b> {
+//!         a: i32,
+//!         b: &'a str = "b",
+//!         c: &'b str = "c",
+//!         d: T?,
+//!     }
+//! }
+//!
+//! let result: Opt<'_, '_, Vec<u8>> = Opt! {4, c = "b", b = "c"};
+//! assert_eq!(
+//!     result,
+//!     Opt {
+//!         a: 4,
+//!         b: "c",
+//!         c: "b",
+//!         d: vec![],
+//!     }
+//! );
+//! ```
+//!
+//! Ending the arguments with `..base`, after at least one field passed by name, uses the struct update syntax:
+//! the fields passed by name, required ones included, are taken from the arguments and all the others from `base`,
+//! instead of using their default values:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[derive(Debug, PartialEq)]
+//!     struct Point {
+//!         x: i32,
+//!         y: i32 = 1,
+//!         z: i32?,
+//!     }
+//! }
+//!
+//! let base = Point!(4, z = 2);
+//! assert_eq!(Point!(x = 5, ..base), Point { x: 5, y: 1, z: 2 });
+//! ```
+//!
+//! With the `serde_defaults` attribute, each optional field is annotated with `#[serde(default)]`,
+//! so that deserializing a struct with `serde` uses the same default values of the macro.
+//! Explicit default values are returned by hidden functions called `{struct}_default_{field}`:
+//! ```
+//! # use opt_args::*;
+//! # use serde::Deserialize;
+//! #
+//! opt_args! {
+//!     #[opt_args(serde_defaults)]
+//!     #[derive(Debug, PartialEq, Deserialize)]
+//!     struct Config {
+//!         name: String,
+//!         retries: u8 = 3,
+//!         verbose: bool?,
+//!     }
+//! }
+//!
+//! let config: Config = serde_json::from_str(r#"{"name": "a"}"#).unwrap();
+//! assert_eq!(config, Config!("a".to_string()));
+//! ```
+//!
+//! ## Validation
+//! For invariants involving more fields, the `validate_at_build` attribute takes the path of a function
+//! receiving the instantiated struct and returning a `Result`, which is returned by the macro instead of the struct.
+//! The path is resolved like `item_path`:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(validate_at_build = check)]
+//!     #[derive(Debug, PartialEq)]
+//!     struct Range {
+//!         start: u32 = 0,
+//!         end: u32 = 10,
+//!     }
+//! }
+//!
+//! fn check(range: Range) -> Result<Range, String> {
+//!     if range.start <= range.end {
+//!         Ok(range)
+//!     } else {
+//!         Err("empty range".to_string())
+//!     }
+//! }
+//!
+//! assert_eq!(Range!(start = 5), Ok(Range { start: 5, end: 10 }));
+//! assert_eq!(Range!(start = 11), Err("empty range".to_string()));
+//! ```
+//!
+//! ## Unused instances
+//! An unused struct expression is not reported by the compiler. With the `must_use` attribute,
+//! the instance created by the macro is reported by the `unused_must_use` lint when it's ignored:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(must_use)]
+//!     struct Point {
+//!         x: i32,
+//!         y: i32 = 1,
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let _point = Point!(4);
+//! # }
+//! ```
+//! ```compile_fail
+//! # #![deny(unused_must_use)]
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(must_use)]
+//!     struct Point {
+//!         x: i32,
+//!         y: i32 = 1,
+//!     }
+//! }
+//!
+//! # fn main() {
+//! Point!(4);
+//! # }
+//! ```
+//!
+//! ## Builder
+//! With the `builder` attribute, instead of the macro a builder named `{struct}Builder` is generated.
+//! `builder` takes the required fields, each `with_` method sets an optional field
+//! and `build` instantiates the struct, using the default value of the optional fields that were not set:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(builder)]
+//!     #[derive(Debug, PartialEq)]
+//!     struct Point {
+//!         x: i32,
+//!         y: i32 = 1,
+//!         z: i32?,
+//!     }
+//! }
+//!
+//! assert_eq!(Point::builder(4).with_z(2).build(), Point { x: 4, y: 1, z: 2 });
+//! ```
+//!
+//! ## Tuple structs
+//! The fields of a tuple struct have no name, so they can only be passed by position,
+//! omitting the trailing optional fields. For this reason, `shuffle`, `positional`,
+//! `builder` and `serde_defaults` are not supported for tuple structs:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[derive(Debug, PartialEq)]
+//!     struct Color(u8, u8, u8 = 255);
+//! }
+//!
+//! assert_eq!(Color!(10, 20), Color(10, 20, 255));
+//! assert_eq!(Color!(10, 20, 30), Color(10, 20, 30));
+//! ```
+//! ```compile_fail
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[opt_args(shuffle)]
+//!     struct Color(u8, u8, u8 = 255);
+//! }
+//! ```
+//!
+//! # Enums
+//! The fields of struct-like variants can have default values too. A single macro is generated
+//! for the whole enum, and the name of the variant is passed as its first argument.
+//! Tuple and unit variants are left unchanged:
+//! ```
+//! # use opt_args::*;
+//! #
+//! opt_args! {
+//!     #[derive(Debug, PartialEq)]
+//!     enum E {
+//!         Unit,
+//!         Variant { a: i32, b: u8 = 3, c: u8? },
+//!     }
+//! }
+//!
+//! assert_eq!(E!(Variant, 1, c = 4), E::Variant { a: 1, b: 3, c: 4 });
+//! ```
+//! Passing a variant without fields with default values results in a compile error:
 //! ```compile_fail
 //! # use opt_args::*;
 //! #
-//! # // doesn't implement `Default`
-//! # struct X {
-//! #     x: usize
-//! # }
-//! #
-//! # opt_args! {
-//! #     fn f(a: X = X { x: 0 }, b: X?) -> usize {
-//! #         a.x + b.x
-//! #     }
-//! # }
-//! f!();
-//! ```
-//! This would result in a call to `f(X { x: 0 }, Default::default())` which would trigger the compile error:
-//! ```the trait `Default` is not implemented for `X` ```.
+//! opt_args! {
+//!     enum E {
+//!         Unit,
+//!         Variant { a: i32, b: u8 = 3 },
+//!     }
+//! }
 //!
-//! This may be useful to force the caller to pass the argument `b` as a named argument.
+//! let e = E!(Unit);
+//! ```
 //!
-//! # Structs
-//! The syntax and usage of the macro for structs is the same as it is for functions:
+//! # Modules
+//! An inline module can be wrapped as well, generating a macro for each item with optional arguments
+//! it contains, including the items of nested modules and `impl` blocks.
+//! The macros are declared outside the module and call the items through its path,
+//! relative to the place where the macro is used, so the items must be visible from there:
 //! ```
 //! # use opt_args::*;
 //! #
 //! opt_args! {
-//!     #[opt_args(shuffle)]
-//!     #[derive(Debug, PartialEq, Eq)]
-//!     struct Opt<'a, 'b, T: 'b> {
-//!         a: i32,
-//!         b: &'a str = "b",
-//!         c: &'b str = "c",
-//!         d: T?,
+//!     mod shapes {
+//!         pub fn area(width: u32, height: u32 = width) -> u32 {
+//!             width * height
+//!         }
+//!
+//!         #[derive(Debug, PartialEq)]
+//!         pub struct Rect {
+//!             pub width: u32 = 1,
+//!             pub height: u32 = 1,
+//!         }
 //!     }
 //! }
 //!
-//! let result: Opt<'_, '_, Vec<u8>> = Opt! {4, c = "b", b = "c"};
-//! assert_eq!(
-//!     result,
-//!     Opt {
-//!         a: 4,
-//!         b: "c",
-//!         c: "b",
-//!         d: vec![],
-//!     }
-//! );
+//! assert_eq!(area!(2), 4);
+//! assert_eq!(Rect!(height = 2), shapes::Rect { width: 1, height: 2 });
 //! ```
+//! Options of the module must be applied to each item.
+use std::collections::HashMap;
 
+use heck::{ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+use itertools::Itertools;
 use proc_macro::TokenStream as TokenStream1;
 use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, spanned::Spanned, Error};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Error, Expr, Generics, Ident, Type, TypePath,
+    Visibility,
+};
 
 use crate::{
-    functions::{compute_combinations, macro_branches},
-    parser::{GenericOptArg, OptArgsAttributes, OptArgsItem, OptArgsItemType},
+    functions::{
+        assert_default, assert_default_type, builder_fn, builder_struct, compute_combinations,
+        count_combinations, fallback, fn_wrapper, helper, is_redundant_default, macro_branches,
+        macro_path, presence_flag_idents, registration, render, serde_defaults, uses_args,
+        uses_control_flow, uses_generics, warning, MacroInfo,
+    },
+    parser::{
+        GenericOptArg, OptArgsArgAttributes, OptArgsAttributes, OptArgsBlockAttributes,
+        OptArgsItem, OptArgsItemEnumFields, OptArgsItemFn, OptArgsItemImplItem, OptArgsItemModItem,
+        OptArgsItemStruct, OptArgsItemType, OptArgsItems,
+    },
 };
 
 mod functions;
 mod parser;
+#[cfg(test)]
+mod tests;
 mod tokens;
 
+/// Maximum number of branches generated for the combinations of optional arguments of a `shuffle` macro,
+/// unless `max_permutations` is given
+const DEFAULT_MAX_PERMUTATIONS: u128 = 5040;
+
 /// Wrap the item (function or struct) inside the macro to declare optional arguments
 /// ```
 /// use opt_args::opt_args;
@@ -357,87 +1532,1376 @@ mod tokens;
 /// ```
 #[proc_macro]
 pub fn opt_args(item: TokenStream1) -> TokenStream1 {
-    let item = parse_macro_input!(item as OptArgsItem);
-    internal(item)
+    let items = parse_macro_input!(item as OptArgsItems);
+    expand(items)
         .unwrap_or_else(|e| e.to_compile_error())
         .into()
 }
 
-fn internal(mut opt_args_item: OptArgsItem) -> syn::Result<TokenStream> {
+fn expand(mut opt_args_items: OptArgsItems) -> syn::Result<TokenStream> {
+    let block_attrs: OptArgsBlockAttributes =
+        deluxe::extract_attributes(&mut opt_args_items.attrs)?;
+    if let Some(attr) = opt_args_items.attrs.first() {
+        return Err(Error::new(
+            attr.span(),
+            "Only `#![opt_args(...)]` is allowed as inner attribute",
+        ));
+    }
+    let mut macro_names: Vec<Ident> = vec![];
+    let mut items = vec![];
+    for item in opt_args_items.items {
+        let Expansion {
+            macro_names: names,
+            macros,
+            items: item,
+            rest,
+        } = expand_item(item, &block_attrs, &[])?;
+        for macro_name in names {
+            // two macros with the same name would shadow each other or clash when exported
+            if macro_names.contains(&macro_name) {
+                return Err(Error::new(
+                    macro_name.span(),
+                    format!(
+                        "A macro named `{}` is already generated by this invocation",
+                        macro_name
+                    ),
+                ));
+            }
+            macro_names.push(macro_name);
+        }
+        items.push(quote!(#macros #item #rest));
+    }
+    Ok(quote!(#(#items)*))
+}
+
+/// Expand an item declared inside the given module, relative to the invocation
+fn expand_item(
+    item: OptArgsItem,
+    block_attrs: &OptArgsBlockAttributes,
+    module: &[Ident],
+) -> syn::Result<Expansion> {
+    if let (Some(existing), OptArgsItemType::ItemImpl(_) | OptArgsItemType::ItemMod(_)) =
+        (&item.existing, &item.item)
+    {
+        return Err(Error::new(
+            existing.span(),
+            "`@existing` is not supported for `impl` blocks and modules",
+        ));
+    }
+    match &item.item {
+        OptArgsItemType::ItemImpl(_) => internal_impl(item, block_attrs, module),
+        OptArgsItemType::ItemMod(_) => internal_mod(item, block_attrs, module),
+        _ => internal(item, block_attrs, module, None),
+    }
+}
+
+/// The output generated for a single item, split so that the items of an `impl` block or a module
+/// can stay inside it while their macros are declared outside
+struct Expansion {
+    macro_names: Vec<Ident>,
+    /// the generated macros
+    macros: TokenStream,
+    /// the item itself and its helpers
+    items: TokenStream,
+    /// everything else
+    rest: TokenStream,
+}
+
+/// The `impl` block containing the function that is being expanded
+struct ImplContext<'a> {
+    /// path used to call the functions, without generic arguments
+    path: TokenStream,
+    ident: &'a Ident,
+    generics: &'a Generics,
+}
+
+/// Generate a macro for each associated function of an `impl` block with optional arguments
+fn internal_impl(
+    opt_args_item: OptArgsItem,
+    block_attrs: &OptArgsBlockAttributes,
+    module: &[Ident],
+) -> syn::Result<Expansion> {
     let OptArgsItem {
-        ref mut attrs,
-        item,
+        attrs,
+        unsafety,
+        item: OptArgsItemType::ItemImpl(item_impl),
+        ..
+    } = opt_args_item
+    else {
+        unreachable!("only `impl` blocks are expanded here");
+    };
+    if let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("opt_args")) {
+        return Err(Error::new(
+            attr.span(),
+            "Options of `impl` blocks must be applied to each function",
+        ));
+    }
+    let Type::Path(TypePath { path, .. }) = &item_impl.self_ty else {
+        unreachable!("the type of an `impl` block is parsed as a path");
+    };
+    // generic arguments can't be written in expression position without a turbofish
+    let segments = path.segments.iter().map(|segment| &segment.ident);
+    let context = ImplContext {
+        path: quote!(#(#segments)::*),
+        ident: &item_impl.ident,
+        generics: &item_impl.generics,
+    };
+    let mut names = vec![];
+    let (mut macros, mut items, mut rest) = (vec![], vec![], vec![]);
+    for impl_item in item_impl.items {
+        match impl_item {
+            // functions without optional arguments are left unchanged
+            OptArgsItemImplItem::Fn(item) if has_optional_args(&item) => {
+                let expansion = internal(item, block_attrs, module, Some(&context))?;
+                names.extend(expansion.macro_names);
+                macros.push(expansion.macros);
+                items.push(expansion.items);
+                rest.push(expansion.rest);
+            }
+            impl_item => items.push(quote!(#impl_item)),
+        }
+    }
+    let self_ty = &item_impl.self_ty;
+    let (impl_generics, _, where_clause) = item_impl.generics.split_for_impl();
+    Ok(Expansion {
+        macro_names: names,
+        macros: quote!(#(#macros)*),
+        items: quote!(
+            #(#attrs)*
+            #unsafety impl #impl_generics #self_ty #where_clause {
+                #(#items)*
+            }
+        ),
+        rest: quote!(#(#rest)*),
+    })
+}
+
+/// Generate a macro for each item of a module with optional arguments.
+/// The macros are declared outside the module, calling the items through its path
+fn internal_mod(
+    opt_args_item: OptArgsItem,
+    block_attrs: &OptArgsBlockAttributes,
+    module: &[Ident],
+) -> syn::Result<Expansion> {
+    let OptArgsItem {
+        attrs,
+        vis,
+        item: OptArgsItemType::ItemMod(item_mod),
         ..
-    } = &mut opt_args_item;
+    } = opt_args_item
+    else {
+        unreachable!("only modules are expanded here");
+    };
+    if let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("opt_args")) {
+        return Err(Error::new(
+            attr.span(),
+            "Options of modules must be applied to each item",
+        ));
+    }
+    let mut module = module.to_vec();
+    module.push(item_mod.ident.clone());
+    let mut names = vec![];
+    let (mut macros, mut items) = (vec![], vec![]);
+    for mod_item in item_mod.items {
+        match mod_item {
+            // items without optional arguments and options are left unchanged
+            OptArgsItemModItem::Item(item)
+                if has_optional_args(&item)
+                    || item
+                        .attrs
+                        .iter()
+                        .any(|attr| attr.path().is_ident("opt_args")) =>
+            {
+                let expansion = expand_item(item, block_attrs, &module)?;
+                names.extend(expansion.macro_names);
+                macros.push(expansion.macros);
+                items.push(expansion.items);
+                items.push(expansion.rest);
+            }
+            mod_item => items.push(quote!(#mod_item)),
+        }
+    }
+    let ident = &item_mod.ident;
+    Ok(Expansion {
+        macro_names: names,
+        macros: quote!(#(#macros)*),
+        items: quote!(
+            #(#attrs)*
+            #vis mod #ident {
+                #(#items)*
+            }
+        ),
+        rest: quote!(),
+    })
+}
+
+/// Check whether an item declares any optional argument.
+/// `impl` blocks and modules may contain items with optional arguments
+fn has_optional_args(opt_args_item: &OptArgsItem) -> bool {
+    match &opt_args_item.item {
+        OptArgsItemType::ItemFn(item_fn) => item_fn.inputs.iter().any(|arg| {
+            arg.value.is_some()
+                || arg.named_default.is_some()
+                || matches!(arg.default, Some(Some(_)))
+        }),
+        OptArgsItemType::ItemStruct(item_struct) => item_struct.fields.iter().any(|field| {
+            field.value.is_some()
+                || field.named_default.is_some()
+                || matches!(field.default, Some(Some(_)))
+        }),
+        OptArgsItemType::ItemEnum(item_enum) => {
+            item_enum
+                .variants
+                .iter()
+                .any(|variant| match &variant.fields {
+                    OptArgsItemEnumFields::Named(fields) => fields.iter().any(|field| {
+                        field.value.is_some()
+                            || field.named_default.is_some()
+                            || matches!(field.default, Some(Some(_)))
+                    }),
+                    _ => false,
+                })
+        }
+        OptArgsItemType::ItemImpl(_) | OptArgsItemType::ItemMod(_) => true,
+    }
+}
+
+fn internal(
+    mut opt_args_item: OptArgsItem,
+    block_attrs: &OptArgsBlockAttributes,
+    module: &[Ident],
+    context: Option<&ImplContext>,
+) -> syn::Result<Expansion> {
+    let parsed_attrs: OptArgsAttributes = deluxe::extract_attributes(&mut opt_args_item.attrs)?;
+    let arg_options = arg_options(&mut opt_args_item)?;
+    let OptArgsItem { vis, item, .. } = &opt_args_item;
     let ident = item.ident().clone();
-    let parsed_attrs: OptArgsAttributes = deluxe::extract_attributes(attrs)?;
+    // the macros of associated functions are named after the type too
+    let base_ident = match context {
+        Some(context) => format_ident!("{}_{}", context.ident, ident),
+        None => ident.clone(),
+    };
+    // the generic parameters of the `impl` block are in scope too
+    let mut generics = item.generics().clone();
+    if let Some(context) = context {
+        generics
+            .params
+            .extend(context.generics.params.iter().cloned());
+    }
     let shuffle = parsed_attrs.shuffle.is_some();
-    let macro_export = (parsed_attrs.non_export.is_none()).then_some(quote!(#[macro_export]));
-    let macro_ident = if let Some(ident) = parsed_attrs.rename {
-        ident
+    let tuple = matches!(
+        item,
+        OptArgsItemType::ItemStruct(OptArgsItemStruct { tuple: true, .. })
+    );
+    if tuple {
+        let options = [
+            ("shuffle", parsed_attrs.shuffle),
+            ("positional", parsed_attrs.positional),
+            ("builder", parsed_attrs.builder),
+            ("serde_defaults", parsed_attrs.serde_defaults),
+        ];
+        if let Some((option, _)) = options.iter().find(|(_, value)| value.is_some()) {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "`{}` is not supported for tuple structs, whose fields can only be passed by position",
+                    option
+                ),
+            ));
+        }
+    }
+    // an existing item can't be modified, since it isn't emitted again
+    if let Some(existing) = &opt_args_item.existing {
+        let options = [
+            ("presence_flags", parsed_attrs.presence_flags),
+            ("serde_defaults", parsed_attrs.serde_defaults),
+        ];
+        if let Some((option, _)) = options.iter().find(|(_, value)| value.is_some()) {
+            return Err(Error::new(
+                existing.span(),
+                format!("`{}` can't be used for `@existing` items", option),
+            ));
+        }
+    }
+    let receiver = matches!(
+        item,
+        OptArgsItemType::ItemFn(OptArgsItemFn {
+            receiver: Some(_),
+            ..
+        })
+    );
+    if receiver && context.is_none() {
+        return Err(Error::new(
+            ident.span(),
+            "A `self` receiver is only supported for functions inside `impl` blocks",
+        ));
+    }
+    // no macro is generated with a different call convention
+    let macro_export = (parsed_attrs.non_export.is_none()
+        && parsed_attrs.call_convention.is_none())
+    .then_some(quote!(#[macro_export]));
+    let doc_hidden = parsed_attrs
+        .doc_hidden
+        .is_some()
+        .then_some(quote!(#[doc(hidden)]));
+    let macro_ident = if let Some(ident) = &parsed_attrs.rename {
+        ident.clone()
     } else {
-        item.ident().clone()
+        let macro_ident = if let Some(rule) = &block_attrs.rename_all_macros {
+            rename_with_rule(rule, &base_ident)?
+        } else {
+            base_ident
+        };
+        let macro_ident = affix(
+            parsed_attrs.prefix.as_ref(),
+            parsed_attrs.suffix.as_ref(),
+            &macro_ident,
+        )?;
+        match &parsed_attrs.macro_case {
+            Some(case) => convert_case(case, &macro_ident)?,
+            None => macro_ident,
+        }
     };
 
-    // convert the list of attributes in a list of generic required/optional arguments
-    let mut args: Vec<_> = match item {
-        OptArgsItemType::ItemFn(item_fn) => item_fn
-            .inputs
-            .clone()
-            .into_iter()
-            .map(GenericOptArg::from)
+    let mut assertions = vec![];
+    // an exported macro can't call an item that is only visible inside a specific module
+    if let (Some(_), Visibility::Restricted(restricted)) = (&macro_export, vis) {
+        if !restricted.path.is_ident("crate") {
+            let path = &restricted.path;
+            assertions.push(warning(
+                vis.span(),
+                &format!(
+                    "`{}` is only visible in `{}`, so the exported macro can't be used outside of it. \
+                    Consider using `#[opt_args(non_export)]`",
+                    ident,
+                    quote!(#path).to_string().replace(' ', ""),
+                ),
+            ));
+        }
+    }
+
+    // convert the list of attributes in a list of generic required/optional arguments.
+    // Each struct-like variant of an enum has its own list
+    let arg_lists: Vec<(Option<&Ident>, Vec<GenericOptArg>)> = match item {
+        // the receiver of a method is the first required argument of the macro
+        OptArgsItemType::ItemFn(item_fn) => vec![(
+            None,
+            item_fn
+                .receiver
+                .iter()
+                .map(|receiver| GenericOptArg::receiver(&receiver.ty))
+                .chain(item_fn.inputs.clone().into_iter().map(GenericOptArg::from))
+                .collect(),
+        )],
+        OptArgsItemType::ItemStruct(item_struct) => vec![(
+            None,
+            item_struct
+                .fields
+                .clone()
+                .into_iter()
+                .map(GenericOptArg::from)
+                .collect(),
+        )],
+        OptArgsItemType::ItemImpl(_) | OptArgsItemType::ItemMod(_) => {
+            unreachable!("the items of `impl` blocks and modules are expanded one at a time")
+        }
+        OptArgsItemType::ItemEnum(item_enum) => item_enum
+            .variants
+            .iter()
+            .filter_map(|variant| match &variant.fields {
+                OptArgsItemEnumFields::Named(fields) => Some((
+                    Some(&variant.ident),
+                    fields.iter().cloned().map(GenericOptArg::from).collect(),
+                )),
+                _ => None,
+            })
             .collect(),
-        OptArgsItemType::ItemStruct(item_struct) => item_struct
-            .fields
-            .clone()
-            .into_iter()
-            .map(GenericOptArg::from)
+    };
+    let mut variants = vec![];
+    for (variant, args) in arg_lists {
+        let options: Vec<_> = arg_options
+            .iter()
+            .filter(|(options_variant, ..)| options_variant.as_ref() == variant)
+            .map(|(_, ident, options)| (ident, options))
+            .collect();
+        let (args, opt_args) = split_args(
+            args,
+            &options,
+            &parsed_attrs,
+            block_attrs,
+            &generics,
+            &mut assertions,
+        )?;
+        variants.push((variant, args, opt_args));
+    }
+    // keyword arguments can only be passed by name to the macro
+    if tuple || parsed_attrs.builder.is_some() || parsed_attrs.call_convention.is_some() {
+        let keyword = variants
+            .iter()
+            .flat_map(|(_, _, opt_args)| opt_args)
+            .find(|arg| arg.keyword);
+        if let Some(arg) = keyword {
+            return Err(Error::new(
+                arg.ident.span(),
+                "`required` is not supported by tuple structs, `builder` and `call_convention`",
+            ));
+        }
+    }
+    if tuple {
+        let named_only = variants
+            .iter()
+            .flat_map(|(_, _, opt_args)| opt_args)
+            .find(|arg| arg.named_only);
+        if let Some(arg) = named_only {
+            return Err(Error::new(
+                arg.ident.span(),
+                "`keyword` is not supported for tuple structs, whose fields can only be passed by position",
+            ));
+        }
+    }
+    let const_generics = const_generics(
+        item.generics(),
+        &parsed_attrs.const_generics_defaults,
+        &variants,
+    )?;
+    // an enum has no arguments itself
+    let (args, opt_args) = match item {
+        OptArgsItemType::ItemEnum(_) => (vec![], vec![]),
+        _ => {
+            let (_, args, opt_args) = &variants[0];
+            (args.clone(), opt_args.clone())
+        }
+    };
+
+    let presence_flags = parsed_attrs.presence_flags.is_some();
+    if presence_flags && !matches!(item, OptArgsItemType::ItemFn(_)) {
+        return Err(Error::new(
+            item.ident().span(),
+            "`presence_flags` is only supported for functions",
+        ));
+    }
+    let flags = presence_flag_idents(&opt_args, presence_flags);
+
+    if parsed_attrs.must_use.is_some() && !matches!(item, OptArgsItemType::ItemStruct(_)) {
+        return Err(Error::new(
+            ident.span(),
+            "`must_use` is only supported for structs, mark functions with `#[must_use]` instead",
+        ));
+    }
+    if parsed_attrs.must_use.is_some() && parsed_attrs.builder.is_some() {
+        return Err(Error::new(
+            ident.span(),
+            "`must_use` can't be used together with `builder`",
+        ));
+    }
+    if let Some(validator) = &parsed_attrs.validate_at_build {
+        if !matches!(item, OptArgsItemType::ItemStruct(_)) {
+            return Err(Error::new(
+                validator.span(),
+                "`validate_at_build` is only supported for structs",
+            ));
+        }
+        if parsed_attrs.builder.is_some() {
+            return Err(Error::new(
+                validator.span(),
+                "`validate_at_build` can't be used together with `builder`",
+            ));
+        }
+    }
+
+    // defaults evaluated outside of the macro can't propagate their errors to the caller
+    if parsed_attrs.r#try.is_some() {
+        let options = [
+            ("builder", parsed_attrs.builder.is_some()),
+            ("call_convention", parsed_attrs.call_convention.is_some()),
+            ("fn_wrapper", parsed_attrs.fn_wrapper.is_some()),
+            ("serde_defaults", parsed_attrs.serde_defaults.is_some()),
+        ];
+        if let Some((option, _)) = options.iter().find(|(_, value)| *value) {
+            return Err(Error::new(
+                ident.span(),
+                format!("`try` can't be used together with `{}`", option),
+            ));
+        }
+    }
+
+    if parsed_attrs.builder.is_some() {
+        if let Some(call_convention) = &parsed_attrs.call_convention {
+            return Err(Error::new(
+                call_convention.span(),
+                "`builder` can't be used together with `call_convention`",
+            ));
+        }
+        let builder = builder_struct(&opt_args_item, &args, &opt_args)?;
+        return Ok(Expansion {
+            macro_names: vec![],
+            macros: quote!(),
+            items: quote!(#opt_args_item),
+            rest: quote!(
+                #builder
+
+                #(#assertions)*
+            ),
+        });
+    }
+
+    if let Some(call_convention) = &parsed_attrs.call_convention {
+        if call_convention != "builder_fn" {
+            return Err(Error::new(
+                call_convention.span(),
+                "Unknown call convention, expected `builder_fn`",
+            ));
+        }
+        let builder_fn = builder_fn(
+            &opt_args_item,
+            &macro_ident,
+            &args,
+            &opt_args,
+            &parsed_attrs.prelude,
+            presence_flags,
+        )?;
+        if context.is_some() {
+            return Err(Error::new(
+                call_convention.span(),
+                "`builder_fn` is not supported inside `impl` blocks",
+            ));
+        }
+        add_presence_flags(&mut opt_args_item, &flags);
+        return Ok(Expansion {
+            macro_names: vec![],
+            macros: quote!(),
+            items: quote!(#opt_args_item),
+            rest: quote!(
+                #builder_fn
+
+                #(#assertions)*
+            ),
+        });
+    }
+
+    let item_path = parsed_attrs
+        .item_path
+        .as_ref()
+        .map(|path| macro_path(path, macro_export.is_some()));
+    // items of modules are called through the path of the module,
+    // and associated functions through the path of the type
+    let segments: Vec<_> = item_path
+        .into_iter()
+        .chain(module.iter().map(|ident| quote!(#ident)))
+        .chain(context.map(|context| context.path.clone()))
+        .collect();
+    let item_path = (!segments.is_empty()).then(|| quote!(#(#segments)::*));
+    let validator = parsed_attrs
+        .validate_at_build
+        .as_ref()
+        .map(|path| macro_path(path, macro_export.is_some()));
+    // only the macros sorting shuffled arguments are limited by default
+    let max_permutations = match &parsed_attrs.max_permutations {
+        Some(max) => Some((max.base10_parse()?, max.span())),
+        None if shuffle => Some((DEFAULT_MAX_PERMUTATIONS, ident.span())),
+        None => None,
+    };
+    // the exported macro is hidden and imported with the name of the macro,
+    // so that it can also be referenced by path from the crate where it's defined
+    let (definition_ident, reexport) = match parsed_attrs.crate_export {
+        Some(()) if macro_export.is_none() => {
+            return Err(Error::new(
+                ident.span(),
+                "`crate_export` requires the macro to be exported",
+            ));
+        }
+        Some(()) => {
+            let hidden = format_ident!("__opt_args_{}", macro_ident);
+            let vis = match vis {
+                Visibility::Inherited => quote!(pub(crate)),
+                vis => quote!(#vis),
+            };
+            let doc = doc_hidden.clone().unwrap_or(quote!(#[doc(inline)]));
+            let reexport = quote!(
+                #doc
+                #[allow(unused_imports)]
+                #vis use #hidden as #macro_ident;
+            );
+            (hidden, Some(reexport))
+        }
+        None => (macro_ident.clone(), None),
+    };
+    // the branches of an exported macro call it again through a hidden import next to it,
+    // so that it can be called by path or renamed, if the module where it's declared is known
+    let self_call =
+        |name: &Ident, definition: &Ident| match (&macro_export, &parsed_attrs.item_path) {
+            (Some(_), Some(path)) => {
+                let module = macro_path(path, true);
+                let alias = format_ident!("__opt_args_self_{}", name);
+                let import = quote!(
+                    #[doc(hidden)]
+                    #[allow(unused_imports)]
+                    pub use #definition as #alias;
+                );
+                (quote!(#module::#alias), Some(import))
+            }
+            _ => (quote!(#name), None),
+        };
+    let (self_path, self_import) = self_call(&macro_ident, &definition_ident);
+    let mut helpers = vec![];
+    let test_ident = format_ident!("{}_test", macro_ident);
+    // the test macro calls itself the same way, through an import only compiled in tests
+    let (test_self_path, test_self_import) = self_call(&test_ident, &test_ident);
+    let test_self_import = test_self_import.map(|import| quote!(#[cfg(test)] #import));
+    let mut branches = vec![];
+    let mut test_branches = vec![];
+    for (variant, args, opt_args) in &variants {
+        let min_args = match &parsed_attrs.min_args {
+            Some(min_args) => {
+                let value = min_args.base10_parse()?;
+                if value
+                    > opt_args
+                        .iter()
+                        .filter(|arg| !arg.is_hidden() && !arg.keyword)
+                        .count()
+                {
+                    return Err(Error::new(
+                        min_args.span(),
+                        "`min_args` can't be greater than the number of optional arguments",
+                    ));
+                }
+                value
+            }
+            None => 0,
+        };
+
+        // keyword arguments are passed in every branch
+        let visible = opt_args
+            .iter()
+            .filter(|arg| !arg.is_hidden() && !arg.keyword)
+            .count()
+            + const_generics.len();
+        let positional = match parsed_attrs.positional {
+            Some(()) => opt_args
+                .iter()
+                .filter(|arg| !arg.is_hidden() && !arg.keyword && !arg.named_only)
+                .count(),
+            None => 0,
+        };
+        let count = match tuple {
+            true => visible as u128 + 1,
+            false => count_combinations(visible, positional),
+        };
+        if let Some((max_permutations, span)) = max_permutations.filter(|(max, _)| count > *max) {
+            return Err(Error::new(
+                span,
+                format!(
+                    "The macro would have {} branches for the combinations of optional arguments, \
+                    more than the limit of {}. Reduce the number of optional arguments \
+                    or raise the limit with `#[opt_args(max_permutations = ...)]`",
+                    count, max_permutations
+                ),
+            ));
+        }
+
+        // cached default values are computed by the helper, which keeps them in a `static`
+        let mut opt_args = opt_args.clone();
+        for arg in opt_args.iter_mut().filter(|arg| arg.cached) {
+            arg.deferred = true;
+        }
+        let helper_path = if opt_args.iter().any(|arg| arg.deferred) {
+            let (helper, helper_path) = helper(
+                &opt_args_item,
+                *variant,
+                context.is_some(),
+                args,
+                &opt_args,
+                presence_flags,
+            );
+            helpers.push(helper);
+            Some(helper_path)
+        } else {
+            None
+        };
+
+        // with `try`, the omitted fallible default values are unwrapped with `?` at the call site
+        if parsed_attrs.r#try.is_some() {
+            for arg in opt_args
+                .iter_mut()
+                .filter(|arg| arg.is_optional() && !arg.default && !arg.is_hidden())
+            {
+                let value = &arg.value;
+                arg.value = Some(Expr::Verbatim(quote!((#value)?)));
+            }
+        }
+        let opt_args = &opt_args;
+
+        let info = MacroInfo {
+            name: &ident,
+            macro_name: &macro_ident,
+            macro_path: self_path.clone(),
+            variant: *variant,
+            required_args: args,
+            opt_args,
+            is_function: matches!(item, OptArgsItemType::ItemFn(_)),
+            receiver,
+            tuple,
+            shuffle,
+            positional: parsed_attrs.positional.is_some(),
+            item_path: item_path.clone(),
+            helper: helper_path,
+            prelude: &parsed_attrs.prelude,
+            exported: macro_export.is_some(),
+            transparent_errors: parsed_attrs.transparent_errors.is_some(),
+            labeled_calls: parsed_attrs.labeled_calls.is_some(),
+            presence_flags,
+            min_args,
+            require_all_named: parsed_attrs.require_all_named.is_some(),
+            error_message: parsed_attrs.error_message.as_ref(),
+            deprecated: parsed_attrs.deprecated.as_ref(),
+            validator: validator.clone(),
+            must_use: parsed_attrs.must_use.is_some(),
+            generics: &generics,
+            item_generics: item.generics(),
+            const_generics: &const_generics,
+            rng: false,
+        };
+        branches.extend(macro_branches(&info));
+
+        if parsed_attrs.test_helpers.is_some() {
+            // the optional arguments that are not passed are generated by the first argument
+            let opt_args: Vec<_> = opt_args
+                .iter()
+                .cloned()
+                .map(|mut arg| {
+                    if !arg.is_hidden() {
+                        arg.value = Some(Expr::Verbatim(quote!($__opt_args_rng.r#gen())));
+                        arg.deferred = false;
+                    }
+                    arg
+                })
+                .collect();
+            test_branches.extend(macro_branches(&MacroInfo {
+                macro_name: &test_ident,
+                macro_path: test_self_path.clone(),
+                opt_args: &opt_args,
+                helper: None,
+                rng: true,
+                ..info
+            }));
+        }
+    }
+    // the branches of each variant only match the calls starting with its name
+    if let OptArgsItemType::ItemEnum(_) = item {
+        let fallback = fallback(parsed_attrs.error_message.as_ref(), quote!($($tt)*));
+        branches.push(quote!(($($tt:tt)*) => {#fallback}));
+        test_branches.push(quote!(($($tt:tt)*) => {#fallback}));
+    }
+    let test_helpers = parsed_attrs.test_helpers.map(|()| {
+        quote!(
+            #[cfg(test)]
+            #[allow(non_snake_case, unused)]
+            #macro_export
+            #doc_hidden
+            #[rustfmt::skip]
+            macro_rules! #test_ident {
+                #(#test_branches);*
+            }
+
+            #test_self_import
+        )
+    });
+
+    let doc_example = match parsed_attrs.doc_example {
+        // non-exported macros can't be used by doctests
+        Some(()) if macro_export.is_none() => {
+            return Err(Error::new(
+                ident.span(),
+                "`doc_example` requires the macro to be exported",
+            ));
+        }
+        Some(()) => Some(doc_example(&macro_ident, &variants, tuple)),
+        None => None,
+    };
+    // associated functions are named after their type
+    let item_name = context
+        .map(|context| context.ident.to_string())
+        .into_iter()
+        .chain([ident.to_string()])
+        .join("::");
+    let doc = macro_doc(item, &item_name, &variants, &const_generics);
+    let snapshot_arms = parsed_attrs.snapshot_arms.map(|()| {
+        let const_ident = format_ident!("{}_ARMS", macro_ident.to_string().to_shouty_snake_case());
+        let arms = snapshot_arms(&macro_ident, &variants, &const_generics, tuple);
+        quote!(
+            #[allow(dead_code)]
+            #vis const #const_ident: &str = #arms;
+        )
+    });
+    let registrations: Vec<_> = match &parsed_attrs.register {
+        Some(registry) => variants
+            .iter()
+            .map(|(variant, _, opt_args)| {
+                let name = [item_name.clone()]
+                    .into_iter()
+                    .chain(variant.map(ToString::to_string))
+                    .join("::");
+                registration(registry, &name, opt_args)
+            })
             .collect(),
+        None => vec![],
+    };
+    let doc_hidden = reexport
+        .as_ref()
+        .map(|_| quote!(#[doc(hidden)]))
+        .or(doc_hidden);
+
+    let serde_defaults = match parsed_attrs.serde_defaults {
+        Some(()) => Some(serde_defaults(&mut opt_args_item, &opt_args)?),
+        None => None,
+    };
+    let fn_wrapper = match parsed_attrs.fn_wrapper {
+        Some(()) if context.is_some() => {
+            return Err(Error::new(
+                ident.span(),
+                "`fn_wrapper` is not supported inside `impl` blocks",
+            ));
+        }
+        Some(()) if presence_flags => {
+            return Err(Error::new(
+                ident.span(),
+                "`fn_wrapper` can't be used together with `presence_flags`",
+            ));
+        }
+        Some(()) => Some(fn_wrapper(&opt_args_item, &args, &opt_args)?),
+        None => None,
     };
+
+    add_presence_flags(&mut opt_args_item, &flags);
+    let macros = quote!(
+        #doc
+        #doc_example
+        #[allow(non_snake_case, unused)]
+        #macro_export
+        #doc_hidden
+        #[rustfmt::skip]
+        macro_rules! #definition_ident {
+            #(#branches);*
+        }
+
+        #reexport
+
+        #self_import
+
+        #test_helpers
+    );
+    Ok(Expansion {
+        macro_names: vec![macro_ident],
+        macros,
+        items: quote!(
+            #opt_args_item
+
+            #(#helpers)*
+        ),
+        rest: quote!(
+            #serde_defaults
+
+            #fn_wrapper
+
+            #(#registrations)*
+
+            #snapshot_arms
+
+            #(#assertions)*
+        ),
+    })
+}
+
+/// Generate the documentation of the macro, listing the arguments of each variant
+/// with the type and the default value of the optional ones
+fn macro_doc(
+    item: &OptArgsItemType,
+    name: &str,
+    variants: &[(Option<&Ident>, Vec<GenericOptArg>, Vec<GenericOptArg>)],
+    const_generics: &[GenericOptArg],
+) -> TokenStream {
+    let mut lines = vec![match item {
+        OptArgsItemType::ItemFn(_) => format!("Calls `{}` with optional arguments.", name),
+        OptArgsItemType::ItemEnum(_) => format!(
+            "Instantiates a variant of `{}` with optional fields, passing its name first.",
+            name
+        ),
+        _ => format!("Instantiates `{}` with optional fields.", name),
+    }];
+    for (variant, args, opt_args) in variants {
+        lines.push(String::new());
+        lines.push(match variant {
+            Some(variant) => format!("# `{}`", variant),
+            None => "# Arguments".to_string(),
+        });
+        for arg in args {
+            lines.push(format!("- `{}: {}`", arg.ident, render(&arg.ty)));
+        }
+        for arg in opt_args.iter().filter(|arg| !arg.is_hidden()) {
+            if arg.keyword {
+                lines.push(format!(
+                    "- `{}: {}` (required, by name)",
+                    arg.ident,
+                    render(&arg.ty)
+                ));
+                continue;
+            }
+            let value = match arg.default {
+                true => "Default::default()".to_string(),
+                false => render(&arg.value),
+            };
+            lines.push(format!(
+                "- `{}: {}` (optional{}, default `{}`)",
+                arg.ident,
+                render(&arg.ty),
+                if arg.named_only { ", by name" } else { "" },
+                value
+            ));
+        }
+    }
+    if !const_generics.is_empty() {
+        lines.push(String::new());
+        lines.push("# Const generic parameters".to_string());
+        for param in const_generics {
+            lines.push(format!(
+                "- `{}: {}` (optional, default `{}`)",
+                param.ident,
+                render(&param.ty),
+                render(&param.value)
+            ));
+        }
+    }
+    let doc = lines.join("\n");
+    quote!(#[doc = #doc])
+}
+
+/// List the calls accepted by the macro, one per line, passing each combination of optional arguments by name
+fn snapshot_arms(
+    macro_ident: &Ident,
+    variants: &[(Option<&Ident>, Vec<GenericOptArg>, Vec<GenericOptArg>)],
+    const_generics: &[GenericOptArg],
+    tuple: bool,
+) -> String {
+    let mut lines = vec![];
+    for (variant, args, opt_args) in variants {
+        let required: Vec<_> = variant
+            .iter()
+            .map(ToString::to_string)
+            .chain(args.iter().map(|arg| arg.ident.to_string()))
+            .collect();
+        let visible: Vec<_> = opt_args
+            .iter()
+            .filter(|arg| !arg.is_hidden())
+            .chain(const_generics)
+            .collect();
+        // the fields of a tuple struct are passed by position
+        let combinations = match tuple {
+            true => (0..=visible.len())
+                .map(|k| {
+                    visible[..k]
+                        .iter()
+                        .map(|arg| arg.ident.to_string())
+                        .collect()
+                })
+                .collect(),
+            false => compute_combinations(&visible)
+                .into_iter()
+                .map(|combination| {
+                    combination
+                        .into_iter()
+                        .map(|ident| format!("{} = ..", ident))
+                        .collect()
+                })
+                .collect::<Vec<Vec<_>>>(),
+        };
+        for combination in combinations {
+            let call_args: Vec<_> = required.iter().cloned().chain(combination).collect();
+            lines.push(format!("{}!({})", macro_ident, call_args.join(", ")));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Generate the documentation of the macro, with an example calling it for each variant
+/// with placeholder values, first passing only the required arguments and then one optional argument too
+fn doc_example(
+    macro_ident: &Ident,
+    variants: &[(Option<&Ident>, Vec<GenericOptArg>, Vec<GenericOptArg>)],
+    tuple: bool,
+) -> TokenStream {
+    let mut lines = vec![
+        String::new(),
+        "# Examples".to_string(),
+        "```no_run".to_string(),
+    ];
+    // exported macros are in the root of the crate
+    if let Ok(name) = std::env::var("CARGO_CRATE_NAME") {
+        lines.push(format!("# use {}::*;", name));
+    }
+    for (variant, args, opt_args) in variants {
+        // keyword arguments are passed in both calls, in the order of declaration
+        let call = |chosen: Option<&Ident>| {
+            let named = opt_args
+                .iter()
+                .filter(|arg| arg.keyword || Some(&arg.ident) == chosen)
+                .map(|arg| match tuple {
+                    true => arg.ident.to_string(),
+                    false => format!("{} = {}", arg.ident, arg.ident),
+                });
+            let call_args: Vec<_> = variant
+                .iter()
+                .map(ToString::to_string)
+                .chain(args.iter().map(|arg| arg.ident.to_string()))
+                .chain(named)
+                .collect();
+            format!("{}!({});", macro_ident, call_args.join(", "))
+        };
+        for arg in args.iter().chain(opt_args.iter().filter(|arg| arg.keyword)) {
+            lines.push(format!("# let {} = todo!();", arg.ident));
+        }
+        lines.push(call(None));
+        if let Some(arg) = opt_args.iter().find(|arg| !arg.is_hidden() && !arg.keyword) {
+            lines.push(format!("# let {} = todo!();", arg.ident));
+            lines.push(call(Some(&arg.ident)));
+        }
+    }
+    lines.push("```".to_string());
+    let doc = lines.join("\n");
+    quote!(#[doc = #doc])
+}
+
+/// Collect the const generic parameters of the item with a default value given by `const_generics_defaults`,
+/// in the order of declaration
+fn const_generics(
+    generics: &Generics,
+    defaults: &HashMap<Ident, Expr>,
+    variants: &[(Option<&Ident>, Vec<GenericOptArg>, Vec<GenericOptArg>)],
+) -> syn::Result<Vec<GenericOptArg>> {
+    let params: Vec<_> = generics.const_params().collect();
+    for ident in defaults.keys() {
+        if !params.iter().any(|param| param.ident == *ident) {
+            return Err(Error::new(
+                ident.span(),
+                format!("`{}` is not a const generic parameter of the item", ident),
+            ));
+        }
+        // const generic parameters are passed by name like the arguments
+        if variants
+            .iter()
+            .flat_map(|(_, args, opt_args)| args.iter().chain(opt_args))
+            .any(|arg| arg.ident == *ident)
+        {
+            return Err(Error::new(
+                ident.span(),
+                format!("`{}` is also the name of an argument", ident),
+            ));
+        }
+    }
+    Ok(params
+        .into_iter()
+        .filter_map(|param| {
+            let value = defaults.get(&param.ident)?;
+            Some(GenericOptArg {
+                ident: param.ident.clone(),
+                ty: Box::new(param.ty.clone()),
+                value: Some(value.clone()),
+                default: false,
+                named_default: None,
+                deferred: false,
+                index: 0,
+                alias: None,
+                keyword: false,
+                named_only: false,
+                cached: false,
+            })
+        })
+        .collect())
+}
+
+/// Split the arguments of an item (or of a variant of an enum) into required and optional arguments,
+/// resolving their default values
+fn split_args(
+    mut args: Vec<GenericOptArg>,
+    options: &[(&Ident, &OptArgsArgAttributes)],
+    parsed_attrs: &OptArgsAttributes,
+    block_attrs: &OptArgsBlockAttributes,
+    generics: &Generics,
+    assertions: &mut Vec<TokenStream>,
+) -> syn::Result<(Vec<GenericOptArg>, Vec<GenericOptArg>)> {
+    let skipped: Vec<_> = options
+        .iter()
+        .filter(|(_, options)| options.skip.is_some())
+        .map(|(ident, _)| *ident)
+        .collect();
+    for (index, arg) in args.iter_mut().enumerate() {
+        arg.index = index;
+        // skipped arguments are passed to the macro as required arguments
+        if skipped.contains(&&arg.ident) {
+            arg.value = None;
+            arg.default = false;
+            arg.named_default = None;
+        }
+        let options = options
+            .iter()
+            .find(|(ident, _)| *ident == &arg.ident)
+            .map(|(_, options)| options);
+        arg.alias = options.and_then(|options| options.alias.clone());
+        arg.keyword = options.is_some_and(|options| options.required.is_some());
+        if arg.keyword && arg.is_optional() {
+            return Err(Error::new(
+                arg.ident.span(),
+                "Arguments marked with `required` can't have a default value",
+            ));
+        }
+        if let Some(options) = options.filter(|options| options.cached.is_some()) {
+            if options.lazy.is_some() {
+                return Err(Error::new(
+                    arg.ident.span(),
+                    "An argument can't be both `lazy` and `cached`",
+                ));
+            }
+            if !arg.is_optional() {
+                return Err(Error::new(
+                    arg.ident.span(),
+                    "Only optional arguments can be `cached`",
+                ));
+            }
+            arg.cached = true;
+        }
+        arg.named_only = options.is_some_and(|options| options.keyword.is_some());
+        if arg.named_only && !arg.is_optional() {
+            return Err(Error::new(
+                arg.ident.span(),
+                "Arguments marked with `keyword` need a default value, use `required` for arguments without one",
+            ));
+        }
+    }
+    check_aliases(&args)?;
+    // replace the references to named default values with their expression
+    for arg in &mut args {
+        if let Some(name) = &arg.named_default {
+            let Some(value) = block_attrs.defaults.get(name) else {
+                return Err(Error::new(
+                    name.span(),
+                    format!(
+                        "Unknown default value `@{}`, declare it with `#![opt_args(defaults({} = ...))]`",
+                        name, name
+                    ),
+                ));
+            };
+            arg.value = Some(value.clone());
+        }
+    }
+    let mut required_args = vec![];
     let mut opt_args = vec![];
-    let mut first_optional = args.len();
     for (a, mut arg) in args.clone().into_iter().enumerate() {
-        // check that all optional arguments are declared after the last non-optional argument
+        // keyword arguments are passed by name like the optional ones
+        if arg.keyword {
+            opt_args.push(arg);
+            continue;
+        }
+        // check that all optional arguments are declared after the last non-optional argument,
+        // except the ones passed only by name
         if !arg.is_optional() {
-            if !opt_args.is_empty() {
+            let positional = opt_args.iter().any(|arg: &GenericOptArg| !arg.named_only);
+            if positional && !skipped.contains(&&arg.ident) {
+                // spans coming from different inputs can't be joined
                 return Err(Error::new(
-                    arg.ident.span().join(arg.ty.span()).unwrap(),
+                    arg.ident
+                        .span()
+                        .join(arg.ty.span())
+                        .unwrap_or(arg.ident.span()),
                     "Non-default arguments should come before default arguments",
                 ));
             }
+            required_args.push(arg);
         } else {
             // if the argument doesn't have an explicit default value, use `Default::default()`
             // (this is not a constraint on the actual type to be implement `Default`,
             // but will only be used in the case of a macro invocation without an explicit value)
             if arg.default {
-                arg.value =
-                    Some(syn::parse(quote!(::std::default::Default::default()).into()).unwrap());
+                if parsed_attrs.assert_defaults.is_some() && !uses_generics(&arg.ty, generics) {
+                    assertions.push(assert_default(&arg));
+                }
+                arg.value = Some(parse_quote!(::std::default::Default::default()));
+            } else if parsed_attrs.suggest_default.is_some()
+                && is_redundant_default(&arg.ty, arg.value.as_ref().unwrap())
+            {
+                let (arg_ident, ty) = (&arg.ident, &arg.ty);
+                assertions.push(warning(
+                    arg.value.span(),
+                    &format!(
+                        "the default value of `{}` is the same as `Default::default()`, \
+                        consider using `{}: {}?`",
+                        arg_ident,
+                        arg_ident,
+                        quote!(#ty).to_string().replace(' ', ""),
+                    ),
+                ));
             }
-            opt_args.push(arg);
-            if first_optional == args.len() {
-                first_optional = a;
+            // default values referencing generic parameters or previous arguments
+            // can only be computed inside the scope of the item
+            arg.deferred = !arg.default
+                && (uses_generics(&arg.value, generics) || uses_args(&arg.value, &args[..a]));
+            if arg.cached && (arg.deferred || uses_generics(&arg.ty, generics)) {
+                return Err(Error::new(
+                    arg.ident.span(),
+                    "`cached` default values can't depend on generic parameters or other arguments",
+                ));
+            }
+            let fallible = parsed_attrs.r#try.is_some() && !arg.default;
+            if fallible && (arg.deferred || arg.cached) {
+                return Err(Error::new(
+                    arg.ident.span(),
+                    "With `try`, default values can't be `cached` or depend on generic parameters or other arguments",
+                ));
+            }
+            // a fallible default value has a different type than the argument,
+            // and `.await` or `?` can only be checked where the macro is called
+            if !arg.default
+                && !arg.deferred
+                && !fallible
+                && !uses_generics(&arg.ty, generics)
+                && !uses_control_flow(&arg.value)
+            {
+                assertions.push(assert_default_type(&arg));
             }
+            opt_args.push(arg);
         }
     }
-    // removes all optional arguments from the original array
-    args.truncate(first_optional);
+    Ok((required_args, opt_args))
+}
 
-    let combinations = compute_combinations(&opt_args, shuffle);
-    let macro_branches = macro_branches(
-        &ident,
-        combinations,
-        &opt_args,
-        &args,
-        matches!(item, OptArgsItemType::ItemFn(_)),
-    );
+/// Apply a `macro_case` conversion to the name of the macro
+fn convert_case(case: &Ident, ident: &Ident) -> syn::Result<Ident> {
+    let name = ident.to_string();
+    let converted = match case.to_string().as_str() {
+        "snake" => name.to_snake_case(),
+        "pascal" => name.to_upper_camel_case(),
+        "keep" => name,
+        _ => {
+            return Err(Error::new(
+                case.span(),
+                "Unknown case, expected one of `snake`, `pascal` or `keep`",
+            ))
+        }
+    };
+    Ok(Ident::new(&converted, ident.span()))
+}
 
-    Ok(quote!(
-        #[allow(non_snake_case, unused)]
-        #macro_export
-        macro_rules! #macro_ident {
-            #(#macro_branches);*
+/// Check that each alias belongs to an optional argument and doesn't clash with the name of another argument
+fn check_aliases(args: &[GenericOptArg]) -> syn::Result<()> {
+    for (a, arg) in args.iter().enumerate() {
+        let Some(alias) = &arg.alias else {
+            continue;
+        };
+        if !arg.is_optional() && !arg.keyword {
+            return Err(Error::new(
+                alias.span(),
+                "Only optional and keyword arguments can have an alias",
+            ));
+        }
+        if args.iter().enumerate().any(|(b, other)| {
+            other.ident == *alias || (a != b && other.alias.as_ref() == Some(alias))
+        }) {
+            return Err(Error::new(
+                alias.span(),
+                format!("`{}` is already the name of an argument", alias),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Options of each argument, together with its name and variant
+type ArgOptions = Vec<(Option<Ident>, Ident, OptArgsArgAttributes)>;
+
+/// Remove the options of the arguments (or fields) from the item, returning them
+fn arg_options(opt_args_item: &mut OptArgsItem) -> syn::Result<ArgOptions> {
+    let mut result = vec![];
+    let mut extract = |variant: Option<&Ident>, ident: &Ident, attrs: &mut Vec<syn::Attribute>| {
+        let arg_attrs: OptArgsArgAttributes = deluxe::extract_attributes(attrs)?;
+        result.push((variant.cloned(), ident.clone(), arg_attrs));
+        syn::Result::Ok(())
+    };
+    match &mut opt_args_item.item {
+        OptArgsItemType::ItemFn(item_fn) => {
+            for arg in &mut item_fn.inputs {
+                extract(None, &arg.ident, &mut arg.attrs)?;
+            }
+        }
+        OptArgsItemType::ItemStruct(item_struct) => {
+            for field in &mut item_struct.fields {
+                extract(None, &field.ident, &mut field.attrs)?;
+            }
         }
+        OptArgsItemType::ItemEnum(item_enum) => {
+            for variant in &mut item_enum.variants {
+                if let OptArgsItemEnumFields::Named(fields) = &mut variant.fields {
+                    for field in fields {
+                        extract(Some(&variant.ident), &field.ident, &mut field.attrs)?;
+                    }
+                }
+            }
+        }
+        OptArgsItemType::ItemImpl(_) | OptArgsItemType::ItemMod(_) => {}
+    }
+    Ok(result)
+}
 
-        #opt_args_item
-    ))
+/// Append a `bool` parameter for each presence flag to the signature of the function,
+/// allowing the function to ignore some of them
+fn add_presence_flags(opt_args_item: &mut OptArgsItem, flags: &[Ident]) {
+    if let OptArgsItemType::ItemFn(item_fn) = &mut opt_args_item.item {
+        item_fn.inputs.extend(
+            flags
+                .iter()
+                .map(|flag| parse_quote!(#[allow(unused_variables)] #flag: bool)),
+        );
+    }
+}
+
+/// Add the `prefix` and `suffix` options to the name of the macro
+fn affix(
+    prefix: Option<&syn::LitStr>,
+    suffix: Option<&syn::LitStr>,
+    ident: &Ident,
+) -> syn::Result<Ident> {
+    if prefix.is_none() && suffix.is_none() {
+        return Ok(ident.clone());
+    }
+    let name = format!(
+        "{}{}{}",
+        prefix.map(syn::LitStr::value).unwrap_or_default(),
+        ident,
+        suffix.map(syn::LitStr::value).unwrap_or_default()
+    );
+    syn::parse_str::<Ident>(&name)
+        .map(|new_ident| Ident::new(&new_ident.to_string(), ident.span()))
+        .map_err(|_| {
+            let span = prefix.or(suffix).unwrap().span();
+            Error::new(
+                span,
+                "The prefix and suffix should produce a valid identifier",
+            )
+        })
+}
+
+/// Apply a `rename_all_macros` rule, replacing every `{}` with the name of the item
+fn rename_with_rule(rule: &syn::LitStr, ident: &Ident) -> syn::Result<Ident> {
+    let rule_value = rule.value();
+    if !rule_value.contains("{}") {
+        return Err(Error::new(
+            rule.span(),
+            "The renaming rule should contain `{}` to indicate the name of the item",
+        ));
+    }
+    syn::parse_str::<Ident>(&rule_value.replace("{}", &ident.to_string()))
+        .map(|new_ident| Ident::new(&new_ident.to_string(), ident.span()))
+        .map_err(|_| {
+            Error::new(
+                rule.span(),
+                "The renaming rule should produce a valid identifier",
+            )
+        })
 }
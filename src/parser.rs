@@ -1,16 +1,46 @@
+use std::collections::HashMap;
+
 use deluxe::ExtractAttributes;
 use derive_syn_parse::Parse;
 use proc_macro2::{Ident, Span};
 use syn::{
-    parse::{Parse, ParseStream},
+    braced, parenthesized,
+    parse::{discouraged::Speculative, Parse, ParseStream},
+    spanned::Spanned,
     token::{Async, Brace, Colon, Const, Paren, Semi, Struct, Unsafe},
-    Abi, Attribute, Block, Error, Expr, Generics, ReturnType, Token, Type, Visibility,
+    Abi, Attribute, Block, Error, Expr, FieldsUnnamed, Generics, ImplItem, Item, LitInt, LitStr,
+    Path, Receiver, ReturnType, Token, Type, TypePath, Visibility,
 };
 
+pub(crate) mod kw {
+    syn::custom_keyword!(existing);
+}
+
+/// The whole content of an [`macro@crate::opt_args`] invocation: optional block-level attributes
+/// (`#![opt_args(...)]`) followed by one or more items
+pub(crate) struct OptArgsItems {
+    pub attrs: Vec<Attribute>,
+    pub items: Vec<OptArgsItem>,
+}
+
+impl Parse for OptArgsItems {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let mut items = vec![input.parse()?];
+        while !input.is_empty() {
+            items.push(input.parse()?);
+        }
+        Ok(Self { attrs, items })
+    }
+}
+
 #[derive(Parse, Clone)]
 pub(crate) struct OptArgsItem {
     #[call(Attribute::parse_outer)]
     pub attrs: Vec<Attribute>,
+    _at: Option<Token![@]>,
+    #[parse_if(_at.is_some())]
+    pub existing: Option<kw::existing>,
     pub vis: Visibility,
     pub constness: Option<Const>,
     pub asyncness: Option<Async>,
@@ -20,9 +50,13 @@ pub(crate) struct OptArgsItem {
 }
 
 #[derive(Clone)]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum OptArgsItemType {
     ItemFn(OptArgsItemFn),
     ItemStruct(OptArgsItemStruct),
+    ItemEnum(OptArgsItemEnum),
+    ItemImpl(OptArgsItemImpl),
+    ItemMod(OptArgsItemMod),
 }
 
 impl OptArgsItemType {
@@ -30,6 +64,19 @@ impl OptArgsItemType {
         match self {
             OptArgsItemType::ItemFn(item_fn) => &item_fn.ident,
             OptArgsItemType::ItemStruct(item_struct) => &item_struct.ident,
+            OptArgsItemType::ItemEnum(item_enum) => &item_enum.ident,
+            OptArgsItemType::ItemImpl(item_impl) => &item_impl.ident,
+            OptArgsItemType::ItemMod(item_mod) => &item_mod.ident,
+        }
+    }
+
+    pub fn generics(&self) -> &Generics {
+        match self {
+            OptArgsItemType::ItemFn(item_fn) => &item_fn.generics,
+            OptArgsItemType::ItemStruct(item_struct) => &item_struct.generics,
+            OptArgsItemType::ItemEnum(item_enum) => &item_enum.generics,
+            OptArgsItemType::ItemImpl(item_impl) => &item_impl.generics,
+            OptArgsItemType::ItemMod(_) => unreachable!("modules have no generic parameters"),
         }
     }
 }
@@ -40,29 +87,55 @@ impl Parse for OptArgsItemType {
             Ok(Self::ItemFn(input.parse()?))
         } else if input.peek(Token![struct]) {
             Ok(Self::ItemStruct(input.parse()?))
+        } else if input.peek(Token![enum]) {
+            Ok(Self::ItemEnum(input.parse()?))
+        } else if input.peek(Token![impl]) {
+            Ok(Self::ItemImpl(input.parse()?))
+        } else if input.peek(Token![mod]) {
+            Ok(Self::ItemMod(input.parse()?))
         } else {
             Err(Error::new(
                 Span::call_site(),
-                "`opt_args` can only be applied to functions or structs",
+                "`opt_args` can only be applied to functions, structs, enums, `impl` blocks or modules",
             ))
         }
     }
 }
 
-#[derive(Parse, Clone)]
+#[derive(Clone)]
 pub(crate) struct OptArgsItemFn {
-    _fn_token: Token![fn],
     pub ident: Ident,
+    /// also contains the `where` clause, which follows the return type
     pub generics: Generics,
-    #[paren]
-    _paren_token: Paren,
-    #[inside(_paren_token)]
-    #[call(parse_vector)]
+    /// the `self` receiver of a method, passed to the macro as its first argument
+    pub receiver: Option<Receiver>,
     pub inputs: Vec<OptArgsItemFnArg>,
     pub output: ReturnType,
     pub block: Box<Block>,
 }
 
+impl Parse for OptArgsItemFn {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![fn]>()?;
+        let ident = input.parse()?;
+        let mut generics: Generics = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+        let (receiver, inputs) = parse_inputs(&content)?;
+        let output = input.parse()?;
+        generics.where_clause = input.parse()?;
+        let block = input.parse()?;
+        Ok(Self {
+            ident,
+            generics,
+            receiver,
+            inputs,
+            output,
+            block,
+        })
+    }
+}
+
 #[derive(Parse, Clone)]
 pub(crate) struct OptArgsItemFnArg {
     #[call(Attribute::parse_outer)]
@@ -72,24 +145,60 @@ pub(crate) struct OptArgsItemFnArg {
     pub ty: Box<Type>,
     _eq: Option<Token![=]>,
     #[parse_if(_eq.is_some())]
+    _at: Option<Option<Token![@]>>,
+    #[parse_if(_at.flatten().is_some())]
+    pub named_default: Option<Ident>,
+    #[parse_if(_eq.is_some() && named_default.is_none())]
     pub value: Option<Expr>,
     #[parse_if(_eq.is_none())]
     pub default: Option<Option<Token![?]>>,
 }
 
-#[derive(Parse, Clone)]
+#[derive(Clone)]
 pub(crate) struct OptArgsItemStruct {
-    _struct_token: Struct,
     pub ident: Ident,
     pub generics: Generics,
-    #[brace]
-    _paren_token: Brace,
-    #[inside(_paren_token)]
-    #[call(parse_vector)]
+    /// the fields of a tuple struct are named after their position, like `_0`
+    pub tuple: bool,
     pub fields: Vec<OptArgsItemStructFields>,
     pub semi_token: Option<Semi>,
 }
 
+impl Parse for OptArgsItemStruct {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Struct>()?;
+        let ident = input.parse()?;
+        let mut generics: Generics = input.parse()?;
+        let content;
+        let (tuple, fields) = if input.peek(Paren) {
+            parenthesized!(content in input);
+            let fields = content
+                .parse_terminated(OptArgsItemStructFields::parse_unnamed, Token![,])?
+                .into_iter()
+                .enumerate()
+                .map(|(index, mut field)| {
+                    field.ident = Ident::new(&format!("_{}", index), field.ty.span());
+                    field
+                })
+                .collect();
+            generics.where_clause = input.parse()?;
+            (true, fields)
+        } else {
+            generics.where_clause = input.parse()?;
+            braced!(content in input);
+            (false, parse_vector(&content)?)
+        };
+        let semi_token = input.parse()?;
+        Ok(Self {
+            ident,
+            generics,
+            tuple,
+            fields,
+            semi_token,
+        })
+    }
+}
+
 #[derive(Parse, Clone)]
 pub(crate) struct OptArgsItemStructFields {
     #[call(Attribute::parse_outer)]
@@ -101,11 +210,213 @@ pub(crate) struct OptArgsItemStructFields {
     pub ty: Type,
     _eq: Option<Token![=]>,
     #[parse_if(_eq.is_some())]
+    _at: Option<Option<Token![@]>>,
+    #[parse_if(_at.flatten().is_some())]
+    pub named_default: Option<Ident>,
+    #[parse_if(_eq.is_some() && named_default.is_none())]
     pub value: Option<Expr>,
     #[parse_if(_eq.is_none())]
     pub default: Option<Option<Token![?]>>,
 }
 
+impl OptArgsItemStructFields {
+    /// Parse a field of a tuple struct, which has no name
+    fn parse_unnamed(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ty: Type = input.parse()?;
+        let _eq: Option<Token![=]> = input.parse()?;
+        let _at: Option<Option<Token![@]>> = _eq.map(|_| input.parse()).transpose()?;
+        let named_default = _at.flatten().map(|_| input.parse()).transpose()?;
+        let value = (_eq.is_some() && named_default.is_none())
+            .then(|| input.parse())
+            .transpose()?;
+        let default = _eq.is_none().then(|| input.parse()).transpose()?;
+        Ok(Self {
+            attrs,
+            vis,
+            mutability: None,
+            // named after the position by the struct
+            ident: Ident::new("_", ty.span()),
+            _colon_token: Colon::default(),
+            ty,
+            _eq,
+            _at,
+            named_default,
+            value,
+            default,
+        })
+    }
+}
+
+#[derive(Parse, Clone)]
+pub(crate) struct OptArgsItemEnum {
+    _enum_token: Token![enum],
+    pub ident: Ident,
+    pub generics: Generics,
+    #[brace]
+    _brace_token: Brace,
+    #[inside(_brace_token)]
+    #[call(parse_vector)]
+    pub variants: Vec<OptArgsItemEnumVariant>,
+}
+
+#[derive(Clone)]
+pub(crate) struct OptArgsItemEnumVariant {
+    pub attrs: Vec<Attribute>,
+    pub ident: Ident,
+    pub fields: OptArgsItemEnumFields,
+    pub discriminant: Option<(Token![=], Expr)>,
+}
+
+/// Only the fields of struct-like variants can have default values,
+/// tuple and unit variants are left unchanged
+#[derive(Clone)]
+pub(crate) enum OptArgsItemEnumFields {
+    Named(Vec<OptArgsItemStructFields>),
+    Unnamed(FieldsUnnamed),
+    Unit,
+}
+
+impl Parse for OptArgsItemEnumVariant {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse()?;
+        let fields = if input.peek(Brace) {
+            let content;
+            braced!(content in input);
+            OptArgsItemEnumFields::Named(parse_vector(&content)?)
+        } else if input.peek(Paren) {
+            OptArgsItemEnumFields::Unnamed(input.parse()?)
+        } else {
+            OptArgsItemEnumFields::Unit
+        };
+        let discriminant = if input.peek(Token![=]) {
+            Some((input.parse()?, input.parse()?))
+        } else {
+            None
+        };
+        Ok(Self {
+            attrs,
+            ident,
+            fields,
+            discriminant,
+        })
+    }
+}
+
+/// An inherent `impl` block, where a macro is generated for each associated function with optional arguments
+#[derive(Clone)]
+pub(crate) struct OptArgsItemImpl {
+    pub generics: Generics,
+    pub self_ty: Type,
+    /// name of the type, used to name the generated macros
+    pub ident: Ident,
+    pub items: Vec<OptArgsItemImplItem>,
+}
+
+#[derive(Clone)]
+pub(crate) enum OptArgsItemImplItem {
+    Fn(OptArgsItem),
+    /// methods with a receiver and any other item, left unchanged
+    Other(ImplItem),
+}
+
+impl Parse for OptArgsItemImpl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![impl]>()?;
+        let mut generics: Generics = input.parse()?;
+        let self_ty: Type = input.parse()?;
+        if input.peek(Token![for]) {
+            return Err(input.error("Only inherent `impl` blocks are supported"));
+        }
+        let Type::Path(TypePath { path, .. }) = &self_ty else {
+            return Err(Error::new(self_ty.span(), "Expected the path of a type"));
+        };
+        let ident = path.segments.last().unwrap().ident.clone();
+        generics.where_clause = input.parse()?;
+        let content;
+        braced!(content in input);
+        let mut items = vec![];
+        while !content.is_empty() {
+            let fork = content.fork();
+            match fork.parse::<OptArgsItem>() {
+                Ok(item) if matches!(item.item, OptArgsItemType::ItemFn(_)) => {
+                    content.advance_to(&fork);
+                    items.push(OptArgsItemImplItem::Fn(item));
+                }
+                result => match content.parse() {
+                    Ok(item) => items.push(OptArgsItemImplItem::Other(item)),
+                    // report why the item can't be parsed with the syntax of `opt_args`
+                    Err(error) => return Err(result.err().unwrap_or(error)),
+                },
+            }
+        }
+        Ok(Self {
+            generics,
+            self_ty,
+            ident,
+            items,
+        })
+    }
+}
+
+/// An inline module, where a macro is generated for each item with optional arguments
+#[derive(Clone)]
+pub(crate) struct OptArgsItemMod {
+    pub ident: Ident,
+    pub items: Vec<OptArgsItemModItem>,
+}
+
+#[derive(Clone)]
+pub(crate) enum OptArgsItemModItem {
+    Item(OptArgsItem),
+    /// any other item, left unchanged
+    Other(Item),
+}
+
+impl Parse for OptArgsItemMod {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![mod]>()?;
+        let ident = input.parse()?;
+        let content;
+        braced!(content in input);
+        let mut items = vec![];
+        while !content.is_empty() {
+            let fork = content.fork();
+            match fork.parse::<OptArgsItem>() {
+                Ok(item) => {
+                    content.advance_to(&fork);
+                    items.push(OptArgsItemModItem::Item(item));
+                }
+                Err(error) => match content.parse() {
+                    Ok(item) => items.push(OptArgsItemModItem::Other(item)),
+                    // report why the item can't be parsed with the syntax of `opt_args`
+                    Err(_) => return Err(error),
+                },
+            }
+        }
+        Ok(Self { ident, items })
+    }
+}
+
+/// Parse the arguments of a function, starting with the `self` receiver of a method, if any
+fn parse_inputs(input: ParseStream) -> syn::Result<(Option<Receiver>, Vec<OptArgsItemFnArg>)> {
+    let receiver = input.peek(Token![self])
+        || input.peek(Token![&])
+        || (input.peek(Token![mut]) && input.peek2(Token![self]));
+    let receiver = if receiver {
+        let receiver = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Some(receiver)
+    } else {
+        None
+    };
+    Ok((receiver, parse_vector(input)?))
+}
+
 fn parse_vector<T: Parse>(input: ParseStream) -> syn::Result<Vec<T>> {
     Ok(input
         .parse_terminated(T::parse, Token![,])?
@@ -119,12 +430,57 @@ pub(crate) struct GenericOptArg {
     pub ty: Box<Type>,
     pub value: Option<Expr>,
     pub default: bool,
+    /// name of a default value declared with `#![opt_args(defaults(...))]`, referenced with `@name`
+    pub named_default: Option<Ident>,
+    /// the default value depends on the scope of the item (generic parameters or `Self`),
+    /// so it's computed by the helper function instead of the macro
+    pub deferred: bool,
+    /// position of the argument in the item, since skipped arguments can be declared among the optionals
+    pub index: usize,
+    /// another name accepted by the macro for this optional argument
+    pub alias: Option<Ident>,
+    /// the argument has no default value and can only be passed by name
+    pub keyword: bool,
+    /// the optional argument can only be passed by name, so it can be declared before the required ones
+    pub named_only: bool,
+    /// the default value is computed once and cloned by each call
+    pub cached: bool,
 }
 
 impl GenericOptArg {
+    /// The receiver of a method, passed to the macro as a required argument named `self_`
+    pub fn receiver(ty: &Type) -> Self {
+        Self {
+            ident: Ident::new("self_", ty.span()),
+            ty: Box::new(ty.clone()),
+            value: None,
+            default: false,
+            named_default: None,
+            deferred: false,
+            index: 0,
+            alias: None,
+            keyword: false,
+            named_only: false,
+            cached: false,
+        }
+    }
+
     pub fn is_optional(&self) -> bool {
         self.value.is_some() || self.default
     }
+
+    /// Optional `PhantomData` arguments are always defaulted and can't be passed to the macro
+    pub fn is_hidden(&self) -> bool {
+        self.is_optional()
+            && matches!(
+                self.ty.as_ref(),
+                Type::Path(path) if path
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "PhantomData")
+            )
+    }
 }
 
 impl From<OptArgsItemFnArg> for GenericOptArg {
@@ -134,6 +490,13 @@ impl From<OptArgsItemFnArg> for GenericOptArg {
             ty: arg.ty,
             value: arg.value,
             default: matches!(arg.default, Some(Some(_))),
+            named_default: arg.named_default,
+            deferred: false,
+            index: 0,
+            alias: None,
+            keyword: false,
+            named_only: false,
+            cached: false,
         }
     }
 }
@@ -145,14 +508,73 @@ impl From<OptArgsItemStructFields> for GenericOptArg {
             ty: Box::new(arg.ty),
             value: arg.value,
             default: matches!(arg.default, Some(Some(_))),
+            named_default: arg.named_default,
+            deferred: false,
+            index: 0,
+            alias: None,
+            keyword: false,
+            named_only: false,
+            cached: false,
         }
     }
 }
 
+#[derive(ExtractAttributes, Debug)]
+#[deluxe(attributes(opt_args))]
+pub(crate) struct OptArgsBlockAttributes {
+    pub rename_all_macros: Option<LitStr>,
+    #[deluxe(default)]
+    pub defaults: HashMap<Ident, Expr>,
+}
+
+/// Options of a single argument or field
+#[derive(ExtractAttributes, Debug)]
+#[deluxe(attributes(opt_args))]
+pub(crate) struct OptArgsArgAttributes {
+    pub skip: Option<()>,
+    pub alias: Option<Ident>,
+    pub required: Option<()>,
+    pub keyword: Option<()>,
+    pub lazy: Option<()>,
+    pub cached: Option<()>,
+}
+
 #[derive(ExtractAttributes, Debug)]
 #[deluxe(attributes(opt_args))]
 pub(crate) struct OptArgsAttributes {
     pub shuffle: Option<()>,
     pub non_export: Option<()>,
+    pub crate_export: Option<()>,
     pub rename: Option<Ident>,
+    pub prefix: Option<LitStr>,
+    pub suffix: Option<LitStr>,
+    pub assert_defaults: Option<()>,
+    pub positional: Option<()>,
+    pub suggest_default: Option<()>,
+    #[deluxe(default)]
+    pub prelude: Vec<Path>,
+    pub item_path: Option<Path>,
+    pub doc_hidden: Option<()>,
+    pub doc_example: Option<()>,
+    pub snapshot_arms: Option<()>,
+    pub transparent_errors: Option<()>,
+    pub labeled_calls: Option<()>,
+    pub presence_flags: Option<()>,
+    pub macro_case: Option<Ident>,
+    pub min_args: Option<LitInt>,
+    pub max_permutations: Option<LitInt>,
+    pub error_message: Option<LitStr>,
+    pub require_all_named: Option<()>,
+    pub deprecated: Option<LitStr>,
+    pub validate_at_build: Option<Path>,
+    pub must_use: Option<()>,
+    pub serde_defaults: Option<()>,
+    pub test_helpers: Option<()>,
+    pub register: Option<Path>,
+    pub call_convention: Option<Ident>,
+    pub builder: Option<()>,
+    pub fn_wrapper: Option<()>,
+    pub r#try: Option<()>,
+    #[deluxe(default)]
+    pub const_generics_defaults: HashMap<Ident, Expr>,
 }
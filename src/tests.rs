@@ -0,0 +1,632 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use quote::quote;
+
+use crate::{expand, parser::OptArgsItems};
+
+/// Expand the body of an `opt_args!` invocation into a file
+fn expand_file(tokens: proc_macro2::TokenStream) -> syn::File {
+    let items: OptArgsItems = syn::parse2(tokens).unwrap();
+    syn::parse2(expand(items).unwrap()).unwrap()
+}
+
+#[test]
+fn formatting_round_trip() {
+    let file = expand_file(quote!(
+        #![opt_args(rename_all_macros = "make_{}")]
+
+        #[opt_args(shuffle, positional, prelude(std::collections::HashMap))]
+        pub fn f<const N: usize>(a: u8, b: [u8; N] = [0; N], c: HashMap<u8, u8>?) -> u8 {
+            a
+        }
+
+        #[opt_args(non_export, transparent_errors)]
+        #[derive(Debug)]
+        struct S<'a, T> {
+            a: &'a str = "a",
+            b: T?,
+        }
+    ));
+    let rustfmt_skip: syn::Path = syn::parse_quote!(rustfmt::skip);
+    let macros = file.items.iter().filter_map(|item| match item {
+        syn::Item::Macro(item) => Some(item),
+        _ => None,
+    });
+    for item in macros {
+        assert!(item.attrs.iter().any(|attr| *attr.path() == rustfmt_skip));
+    }
+
+    // rustfmt may not be installed where the tests are run
+    let Ok(mut rustfmt) = Command::new("rustfmt")
+        .args(["--edition", "2021"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return;
+    };
+    rustfmt
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(quote!(#file).to_string().as_bytes())
+        .unwrap();
+    let output = rustfmt.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn labeled_calls() {
+    let file = expand_file(quote!(
+        #[opt_args(labeled_calls)]
+        fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+            a + b + c
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    // branch matching `f!(1, c = 2)`
+    let branch = quote!(
+        ($a:expr, c = $c:expr) => {{
+            let a = $a;
+            let b = 5;
+            let c = $c;
+            f(a, b, c)
+        }}
+    )
+    .to_string();
+    assert!(item.mac.tokens.to_string().contains(&branch));
+}
+
+#[test]
+fn transparent_errors_nameable_types() {
+    let file = expand_file(quote!(
+        #[opt_args(transparent_errors)]
+        fn f(a: HashMap<u8, u8>, b: Vec<u8>?, c: Option<::std::path::PathBuf>?) -> u8 {
+            0
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    let tokens = item.mac.tokens.to_string();
+    assert!(tokens.contains(&quote!(let a = $a;).to_string()));
+    assert!(tokens.contains(&quote!(let b: Vec<u8> = $b;).to_string()));
+    assert!(tokens.contains(&quote!(let c: Option<::std::path::PathBuf> = $c;).to_string()));
+}
+
+#[test]
+fn required_arguments_only() {
+    let file = expand_file(quote!(
+        impl Foo {
+            fn new(a: u8) -> Self {
+                Self { a }
+            }
+        }
+
+        mod m {
+            pub struct S {
+                pub a: u8,
+            }
+        }
+    ));
+    assert!(!file
+        .items
+        .iter()
+        .any(|item| matches!(item, syn::Item::Macro(_))));
+}
+
+/// Patterns of the branches of the first macro generated by the invocation
+fn branch_patterns(file: &syn::File) -> Vec<String> {
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    item.mac
+        .tokens
+        .clone()
+        .into_iter()
+        .filter_map(|token| match token {
+            proc_macro2::TokenTree::Group(group)
+                if group.delimiter() == proc_macro2::Delimiter::Parenthesis =>
+            {
+                Some(group.stream().to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn deterministic_branches() {
+    let tokens = quote!(
+        #[opt_args(shuffle, positional)]
+        fn f(a: u8, b: u8?, c: u8?) -> u8 {
+            a + b + c
+        }
+    );
+    let first = branch_patterns(&expand_file(tokens.clone()));
+    let second = branch_patterns(&expand_file(tokens));
+    assert_eq!(first, second);
+    assert_eq!(
+        first,
+        [
+            "@ __opt_args_sort 0 $ __opt_args_call : tt $ __opt_args_prefix : tt () $ __opt_args_slot_c : tt ; b = $ __opt_args_value : expr $ (, $ ($ __opt_args_rest : tt) *) ?",
+            "@ __opt_args_sort 0 $ __opt_args_call : tt $ __opt_args_prefix : tt $ __opt_args_slot_b : tt () ; c = $ __opt_args_value : expr $ (, $ ($ __opt_args_rest : tt) *) ?",
+            "@ __opt_args_sort 0 [$ ($ __opt_args_call : tt) *] $ __opt_args_prefix : tt ($ ($ __opt_args_previous : tt) +) $ __opt_args_slot_c : tt ; b = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_sort 0 [$ ($ __opt_args_call : tt) *] $ __opt_args_prefix : tt $ __opt_args_slot_b : tt ($ ($ __opt_args_previous : tt) +) ; c = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_sort 0 $ __opt_args_call : tt ($ ($ __opt_args_prefix : tt) *) ($ ($ __opt_args_slot_b : expr) ?) ($ ($ __opt_args_slot_c : expr) ?) ;",
+            "@ __opt_args_sort 0 [$ ($ __opt_args_call : tt) *] $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_typo $ __opt_args_call : tt b = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_typo $ __opt_args_call : tt c = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_typo $ __opt_args_call : tt $ __opt_args_key : ident = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_typo $ __opt_args_call : tt $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_skip $ __opt_args_call : tt , $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_skip $ __opt_args_call : tt ; $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_skip $ __opt_args_call : tt $ __opt_args_token : tt $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_skip [$ ($ __opt_args_call : tt) *]",
+            "@ __opt_args_repeat $ __opt_args_call : tt () $ __opt_args_slot_c : tt ; b = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat [$ ($ __opt_args_call : tt) *] ($ ($ __opt_args_previous : tt) +) $ __opt_args_slot_c : tt ; b = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat $ __opt_args_call : tt $ __opt_args_slot_b : tt () ; c = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat [$ ($ __opt_args_call : tt) *] $ __opt_args_slot_b : tt ($ ($ __opt_args_previous : tt) +) ; c = $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat $ __opt_args_call : tt $ (($ ($ __opt_args_slot : tt) *)) * ; $ ($ __opt_args_rest : tt) +",
+            "@ __opt_args_repeat [$ ($ __opt_args_call : tt) *] $ (($ ($ __opt_args_slot : tt) *)) * ;",
+            "@ __opt_args_repeat_skip $ __opt_args_call : tt $ (($ ($ __opt_args_slot : tt) *)) * ; , $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat_skip $ __opt_args_call : tt $ (($ ($ __opt_args_slot : tt) *)) * ; ; $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat_skip $ __opt_args_call : tt $ (($ ($ __opt_args_slot : tt) *)) * ; $ __opt_args_token : tt $ ($ __opt_args_rest : tt) *",
+            "@ __opt_args_repeat_skip [$ ($ __opt_args_call : tt) *] $ (($ ($ __opt_args_slot : tt) *)) * ;",
+            "$ a : expr",
+            "$ a : expr , b = $ b : expr",
+            "$ a : expr , c = $ c : expr",
+            "$ a : expr , b = $ b : expr , c = $ c : expr",
+            "$ a : expr , $ ($ __opt_args_key : ident = $ __opt_args_value : expr) , +",
+            "$ a : expr , ..",
+            "$ a : expr , .. , c = $ c : expr",
+            "$ a : expr , $ b : expr",
+            "$ a : expr , $ b : expr , c = $ c : expr",
+            "$ a : expr , .. , ..",
+            "$ a : expr , $ b : expr , ..",
+            "$ a : expr , .. , $ c : expr",
+            "$ a : expr , $ b : expr , $ c : expr",
+            "$ a : expr ; $ ($ __opt_args_key : ident = $ __opt_args_value : expr) , * $ (,) ?",
+            "$ ($ tt : tt) *",
+        ]
+    );
+}
+
+#[test]
+fn serde_defaults() {
+    let file = expand_file(quote!(
+        #[opt_args(serde_defaults)]
+        #[derive(Deserialize)]
+        struct Config {
+            name: String,
+            retries: u8 = 3,
+            verbose: bool?,
+        }
+    ));
+    let syn::Item::Struct(item) = &file.items[1] else {
+        panic!("the struct should follow the macro");
+    };
+    let attrs: Vec<_> = item
+        .fields
+        .iter()
+        .map(|field| {
+            let attrs = &field.attrs;
+            quote!(#(#attrs)*).to_string()
+        })
+        .collect();
+    assert_eq!(
+        attrs,
+        [
+            String::new(),
+            quote!(#[serde(default = "Config_default_retries")]).to_string(),
+            quote!(#[serde(default)]).to_string(),
+        ]
+    );
+    let syn::Item::Fn(function) = &file.items[2] else {
+        panic!("the default function should follow the struct");
+    };
+    assert_eq!(function.sig.ident, "Config_default_retries");
+    assert_eq!(function.sig.output, syn::parse_quote!(-> u8));
+}
+
+#[test]
+fn prefix_suffix_export() {
+    let file = expand_file(quote!(
+        #[opt_args(prefix = "make_", suffix = "_now")]
+        struct Widget {
+            a: u8?,
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    assert_eq!(item.ident.as_ref().unwrap(), "make_Widget_now");
+    let macro_export: syn::Path = syn::parse_quote!(macro_export);
+    assert!(item.attrs.iter().any(|attr| *attr.path() == macro_export));
+}
+
+#[test]
+fn custom_attributes() {
+    // the attribute is never resolved, so any path works as a mock of an attribute macro
+    let file = expand_file(quote!(
+        #[instrument(skip(b))]
+        #[opt_args(shuffle)]
+        #[inline]
+        fn f(a: u8, b: u8?) -> u8 {
+            a + b
+        }
+    ));
+    let syn::Item::Fn(function) = &file.items[1] else {
+        panic!("the function should follow the macro");
+    };
+    let attrs = &function.attrs;
+    assert_eq!(
+        quote!(#(#attrs)*).to_string(),
+        quote!(#[instrument(skip(b))] #[inline]).to_string()
+    );
+}
+
+#[test]
+fn compile_error_fallback() {
+    let file = expand_file(quote!(
+        fn f(a: u8, b: u8?) -> u8 {
+            a + b
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    // reached after looking for unknown and repeated names
+    let fallback = quote!(
+        (@__opt_args_repeat_skip [$($__opt_args_call:tt)*] $(($($__opt_args_slot:tt)*))* ;) => {
+            compile_error!(concat!(
+                "Unrecognized order or name for arguments: `",
+                stringify!($($__opt_args_call)*),
+                "`. If you want to pass named parameters in any order, use the attribute #[opt_args(shuffle)]"
+            ))
+        }
+    )
+    .to_string();
+    assert!(item.mac.tokens.to_string().contains(&fallback));
+}
+
+#[test]
+fn repeated_argument() {
+    for attrs in [quote!(), quote!(#[opt_args(shuffle)])] {
+        let file = expand_file(quote!(
+            #attrs
+            fn f(a: u8, b: u8?, c: u8?) -> u8 {
+                a + b + c
+            }
+        ));
+        let syn::Item::Macro(item) = &file.items[0] else {
+            panic!("the macro should be the first item");
+        };
+        let tokens = item.mac.tokens.to_string();
+        for name in ["b", "c"] {
+            let message = format!("\"Argument `{}` passed more than once to `f!`: `\"", name);
+            assert!(tokens.contains(&message));
+        }
+    }
+}
+
+#[test]
+fn doc_example() {
+    let file = expand_file(quote!(
+        #[opt_args(doc_example)]
+        pub enum E {
+            A { a: u8, b: u8 = 5, c: u8? },
+            B { d: u8? },
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    // the example follows the list of arguments
+    let syn::Meta::NameValue(doc) = &item.attrs[1].meta else {
+        panic!("the second attribute should be the example");
+    };
+    let doc = quote!(#doc).to_string();
+    let example = [
+        "# let a = todo!();",
+        "E!(A, a);",
+        "# let b = todo!();",
+        "E!(A, a, b = b);",
+        "E!(B);",
+        "# let d = todo!();",
+        "E!(B, d = d);",
+        "```",
+    ]
+    .join("\\n");
+    assert!(doc.contains(&example), "{}", doc);
+}
+
+#[test]
+fn max_permutations() {
+    let items: OptArgsItems = syn::parse2(quote!(
+        #[opt_args(shuffle, max_permutations = 100)]
+        fn f(a: u8?, b: u8?, c: u8?, d: u8?, e: u8?, f: u8?, g: u8?, h: u8?) -> u8 {
+            a + b + c + d + e + f + g + h
+        }
+    ))
+    .unwrap();
+    let error = expand(items).unwrap_err().to_string();
+    assert!(error.contains("256 branches"), "{}", error);
+    assert!(error.contains("limit of 100"), "{}", error);
+}
+
+#[test]
+fn default_max_permutations() {
+    let item = |shuffle: Option<proc_macro2::TokenStream>| {
+        let items: OptArgsItems = syn::parse2(quote!(
+            #[opt_args(#shuffle)]
+            fn f(
+                a: u8?, b: u8?, c: u8?, d: u8?, e: u8?, f: u8?, g: u8?,
+                h: u8?, i: u8?, j: u8?, k: u8?, l: u8?, m: u8?,
+            ) {}
+        ))
+        .unwrap();
+        expand(items)
+    };
+    // only `shuffle` macros are limited by default
+    assert!(item(None).is_ok());
+    let error = item(Some(quote!(shuffle))).unwrap_err().to_string();
+    assert!(error.contains("8192 branches"), "{}", error);
+    assert!(error.contains("limit of 5040"), "{}", error);
+}
+
+#[test]
+fn register() {
+    let file = expand_file(quote!(
+        #[opt_args(register = crate::Registry)]
+        enum E {
+            A { a: u8, b: u8 = 5, c: u8? },
+            B { d: u8? },
+        }
+    ));
+    let registrations: Vec<_> = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Macro(item) if item.mac.path.segments.last().unwrap().ident == "submit" => {
+                Some(item.mac.tokens.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    let expected = [
+        quote!(crate::Registry {
+            name: "E::A",
+            module: ::std::module_path!(),
+            optional: &["b", "c"],
+        }),
+        quote!(crate::Registry {
+            name: "E::B",
+            module: ::std::module_path!(),
+            optional: &["d"],
+        }),
+    ]
+    .map(|tokens| tokens.to_string());
+    assert_eq!(registrations, expected);
+}
+
+#[test]
+fn macro_doc() {
+    let file = expand_file(quote!(
+        #[opt_args(const_generics_defaults(N = 4))]
+        fn f<const N: usize>(a: u8, b: Vec<u8> = vec![1], c: [u8; N]?) -> u8 {
+            a
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    let syn::Meta::NameValue(syn::MetaNameValue {
+        value:
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(doc),
+                ..
+            }),
+        ..
+    }) = &item.attrs[0].meta
+    else {
+        panic!("the first attribute should be the documentation");
+    };
+    let expected = [
+        "Calls `f` with optional arguments.",
+        "",
+        "# Arguments",
+        "- `a: u8`",
+        "- `b: Vec<u8>` (optional, default `vec![1]`)",
+        "- `c: [u8; N]` (optional, default `Default::default()`)",
+        "",
+        "# Const generic parameters",
+        "- `N: usize` (optional, default `4`)",
+    ]
+    .join("\n");
+    assert_eq!(doc.value(), expected);
+}
+
+#[test]
+fn misordered_args() {
+    let items: OptArgsItems = syn::parse2(quote!(
+        fn f(a: u8 = 1, b: u8) -> u8 {
+            a + b
+        }
+    ))
+    .unwrap();
+    let error = expand(items).unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "Non-default arguments should come before default arguments"
+    );
+}
+
+#[test]
+fn keyword_without_default() {
+    let items: OptArgsItems = syn::parse2(quote!(
+        fn f(#[opt_args(keyword)] a: u8, b: u8) -> u8 {
+            a + b
+        }
+    ))
+    .unwrap();
+    let error = expand(items).unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "Arguments marked with `keyword` need a default value, use `required` for arguments without one"
+    );
+}
+
+#[test]
+fn unknown_name() {
+    let file = expand_file(quote!(
+        fn f(a: u8, bar: u8 = 5, c: u8?) -> u8 {
+            a + bar + c
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    // the unknown name is accessed as a field, so the error points at it and suggests the closest valid name
+    let branch = quote!(
+        (@__opt_args_typo $__opt_args_call:tt $__opt_args_key:ident = $($__opt_args_rest:tt)*) => {{
+            #[allow(dead_code)]
+            struct FArguments {
+                bar: (),
+                c: ()
+            }
+            let _ = |arguments: FArguments| arguments.$__opt_args_key;
+            unreachable!()
+        }}
+    );
+    assert!(item.mac.tokens.to_string().contains(&branch.to_string()));
+}
+
+#[test]
+fn typo_through_path() {
+    let file = expand_file(quote!(
+        #[opt_args(item_path = crate::shapes)]
+        pub fn f(a: u8, b: u8?) -> u8 {
+            a + b
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    // the macro can be called by path from other crates, where its name may not be in scope
+    let scan = quote!($crate::shapes::__opt_args_self_f!(@__opt_args_typo [$($tt)*] $($tt)*));
+    assert!(item.mac.tokens.to_string().contains(&scan.to_string()));
+}
+
+#[test]
+fn repeated_argument_through_path() {
+    let file = expand_file(quote!(
+        #[opt_args(shuffle, item_path = crate::shapes)]
+        pub fn f(a: u8, b: u8?, c: u8?) -> u8 {
+            a + b + c
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    let tokens = item.mac.tokens.to_string();
+    assert!(tokens.contains("$ crate :: shapes :: __opt_args_self_f ! (@ __opt_args_repeat_skip"));
+    // every branch calls the macro again through its path
+    assert_eq!(
+        tokens.matches("f ! (").count(),
+        tokens.matches("__opt_args_self_f ! (").count()
+    );
+}
+
+#[test]
+fn test_helpers_through_path() {
+    let file = expand_file(quote!(
+        #[opt_args(test_helpers, shuffle, item_path = crate::shapes)]
+        pub fn f(a: u8, b: u8?, c: u8?) -> u8 {
+            a + b + c
+        }
+    ));
+    let item = file
+        .items
+        .iter()
+        .find_map(|item| match item {
+            syn::Item::Macro(item)
+                if item.ident.as_ref().is_some_and(|ident| ident == "f_test") =>
+            {
+                Some(item)
+            }
+            _ => None,
+        })
+        .expect("the test macro should be generated");
+    // the test macro calls itself through its own path, like the main one
+    let tokens = item.mac.tokens.to_string();
+    assert!(tokens.contains("$ crate :: shapes :: __opt_args_self_f_test ! ("));
+    assert_eq!(
+        tokens.matches("f_test ! (").count(),
+        tokens.matches("__opt_args_self_f_test ! (").count()
+    );
+    let import = quote!(
+        pub use f_test as __opt_args_self_f_test;
+    );
+    assert!(quote!(#file).to_string().contains(&import.to_string()));
+}
+
+#[test]
+fn min_args_not_reached() {
+    let file = expand_file(quote!(
+        #[opt_args(min_args = 2, positional, non_export)]
+        fn f(a: u8, b: u8?, c: u8?, d: u8?) -> (u8, u8, u8, u8) {
+            (a, b, c, d)
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    // the branch still matches `f!(1, .., 3)`, but fails to compile instead of panicking
+    let branch = quote!(
+        ($a:expr, .., $c:expr) => {
+            compile_error!("At least 2 optional arguments must be passed to `f!`, found 1")
+        }
+    );
+    let tokens = item.mac.tokens.to_string();
+    assert!(tokens.contains(&branch.to_string()));
+    assert!(!tokens.contains("panic ! (\"At least"));
+}
+
+#[test]
+fn require_all_named_positional() {
+    let file = expand_file(quote!(
+        #[opt_args(positional, require_all_named, non_export)]
+        fn f(a: u8, b: u8 = 5, c: u8?) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+    ));
+    let syn::Item::Macro(item) = &file.items[0] else {
+        panic!("the macro should be the first item");
+    };
+    let tokens = item.mac.tokens.to_string();
+    // every branch passing an optional argument by position is rejected, skips and mixed forms included
+    for pattern in [
+        quote!(($a:expr, $b:expr)),
+        quote!(($a:expr, .., $c:expr)),
+        quote!(($a:expr, $b:expr, c = $c:expr)),
+    ] {
+        let branch = quote!(
+            #pattern => {
+                compile_error!("Optional arguments of `f!` must be passed by name")
+            }
+        );
+        assert!(tokens.contains(&branch.to_string()), "{}", pattern);
+    }
+    assert!(!tokens.contains("panic ! (\"Optional arguments"));
+}
@@ -2,8 +2,9 @@ use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
 
 use crate::parser::{
-    OptArgsItem, OptArgsItemFn, OptArgsItemFnArg, OptArgsItemStruct, OptArgsItemStructFields,
-    OptArgsItemType,
+    OptArgsItem, OptArgsItemEnum, OptArgsItemEnumFields, OptArgsItemEnumVariant, OptArgsItemFn,
+    OptArgsItemFnArg, OptArgsItemImpl, OptArgsItemImplItem, OptArgsItemMod, OptArgsItemModItem,
+    OptArgsItemStruct, OptArgsItemStructFields, OptArgsItemType,
 };
 
 impl ToTokens for OptArgsItem {
@@ -16,40 +17,151 @@ impl ToTokens for OptArgsItem {
             unsafety,
             abi,
             item,
+            existing,
+            ..
         } = self;
+        // the item is already defined outside of the invocation
+        if existing.is_some() {
+            return;
+        }
         match item {
             OptArgsItemType::ItemFn(OptArgsItemFn {
                 ident,
                 generics,
+                receiver,
                 inputs,
                 output,
                 block,
+            }) => {
+                let where_clause = &generics.where_clause;
+                let receiver = receiver.iter();
+                quote!(
+                    #(#attrs)*
+                    #vis #constness #asyncness #unsafety #abi fn #ident
+                    #generics
+                    (#(#receiver,)* #(#inputs),*) #output #where_clause #block
+                )
+                .to_tokens(tokens)
+            }
+            OptArgsItemType::ItemStruct(OptArgsItemStruct {
+                ident,
+                generics,
+                tuple: true,
+                fields,
                 ..
-            }) => quote!(
-                #(#attrs)*
-                #vis #constness #asyncness #unsafety #abi fn #ident
-                #generics
-                (#(#inputs),*) #output #block
-            )
-            .to_tokens(tokens),
+            }) => {
+                let fields = fields.iter().map(
+                    |OptArgsItemStructFields { attrs, vis, ty, .. }| quote!(#(#attrs)* #vis #ty),
+                );
+                let where_clause = &generics.where_clause;
+                quote!(
+                    #(#attrs)*
+                    #vis
+                    struct #ident #generics (
+                        #(#fields),*
+                    ) #where_clause;
+                )
+                .to_tokens(tokens)
+            }
             OptArgsItemType::ItemStruct(OptArgsItemStruct {
                 ident,
                 generics,
                 fields,
                 semi_token,
                 ..
+            }) => {
+                let where_clause = &generics.where_clause;
+                quote!(
+                    #(#attrs)*
+                    #vis
+                    struct #ident #generics #where_clause {
+                        #(#fields),*
+                    } #semi_token
+                )
+                .to_tokens(tokens)
+            }
+            OptArgsItemType::ItemEnum(OptArgsItemEnum {
+                ident,
+                generics,
+                variants,
+                ..
             }) => quote!(
                 #(#attrs)*
                 #vis
-                struct #ident #generics {
-                    #(#fields),*
-                } #semi_token
+                enum #ident #generics {
+                    #(#variants),*
+                }
+            )
+            .to_tokens(tokens),
+            OptArgsItemType::ItemImpl(OptArgsItemImpl {
+                generics,
+                self_ty,
+                items,
+                ..
+            }) => {
+                let (impl_generics, _, where_clause) = generics.split_for_impl();
+                quote!(
+                    #(#attrs)*
+                    #unsafety impl #impl_generics #self_ty #where_clause {
+                        #(#items)*
+                    }
+                )
+                .to_tokens(tokens)
+            }
+            OptArgsItemType::ItemMod(OptArgsItemMod { ident, items }) => quote!(
+                #(#attrs)*
+                #vis
+                mod #ident {
+                    #(#items)*
+                }
             )
             .to_tokens(tokens),
         }
     }
 }
 
+impl ToTokens for OptArgsItemModItem {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            OptArgsItemModItem::Item(item) => item.to_tokens(tokens),
+            OptArgsItemModItem::Other(item) => item.to_tokens(tokens),
+        }
+    }
+}
+
+impl ToTokens for OptArgsItemImplItem {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            OptArgsItemImplItem::Fn(item) => item.to_tokens(tokens),
+            OptArgsItemImplItem::Other(item) => item.to_tokens(tokens),
+        }
+    }
+}
+
+impl ToTokens for OptArgsItemEnumVariant {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let OptArgsItemEnumVariant {
+            attrs,
+            ident,
+            fields,
+            discriminant,
+        } = self;
+        let fields = match fields {
+            OptArgsItemEnumFields::Named(fields) => quote!({ #(#fields),* }),
+            OptArgsItemEnumFields::Unnamed(fields) => quote!(#fields),
+            OptArgsItemEnumFields::Unit => quote!(),
+        };
+        let discriminant = discriminant
+            .as_ref()
+            .map(|(eq_token, value)| quote!(#eq_token #value));
+        quote!(
+            #(#attrs)*
+            #ident #fields #discriminant
+        )
+        .to_tokens(tokens)
+    }
+}
+
 impl ToTokens for OptArgsItemFnArg {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let OptArgsItemFnArg {
@@ -0,0 +1,32 @@
+use opt_args_exported::aliased as renamed_aliased;
+use opt_args_exported::keyed as renamed_keyed;
+use opt_args_exported::shuffled as renamed_shuffled;
+
+#[test]
+fn key_value() {
+    assert_eq!(opt_args_exported::keyed!(1; c = 2), (1, 5, 2));
+    assert_eq!(opt_args_exported::keyed!(1; b = 1, c = 2), (1, 1, 2));
+    assert_eq!(renamed_keyed!(1; b = 1), (1, 1, 0));
+}
+
+#[test]
+fn shuffle() {
+    assert_eq!(opt_args_exported::shuffled!(1, c = 2, b = 1), (1, 1, 2));
+    assert_eq!(opt_args_exported::shuffled!(1, b = 1, c = 2), (1, 1, 2));
+    assert_eq!(renamed_shuffled!(1, c = 2, b = 1), (1, 1, 2));
+}
+
+#[test]
+fn alias() {
+    assert_eq!(opt_args_exported::aliased!(1, old_c = 4), (1, 2, 4));
+    assert_eq!(opt_args_exported::aliased!(1, b = 5, old_c = 4), (1, 5, 4));
+    assert_eq!(renamed_aliased!(1, old_c = 4), (1, 2, 4));
+}
+
+#[test]
+fn cfg_default() {
+    // the default value is evaluated in the crate calling the macro, which is compiled with `--cfg test`
+    assert_eq!(opt_args_exported::default_threads(), 4);
+    assert_eq!(opt_args_exported::workers!(), 1);
+    assert_eq!(opt_args_exported::workers!(threads = 2), 2);
+}
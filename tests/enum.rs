@@ -0,0 +1,77 @@
+use opt_args::opt_args;
+
+#[test]
+fn opt_enum() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        enum E {
+            Unit,
+            Tuple(u8, u8),
+            Variant { a: i32, b: u8 = 3, c: u8? },
+            Other { name: &'static str = "other" },
+        }
+    }
+
+    assert_eq!(E!(Variant, 1), E::Variant { a: 1, b: 3, c: 0 });
+    assert_eq!(E!(Variant, 1, c = 4), E::Variant { a: 1, b: 3, c: 4 });
+    assert_eq!(
+        E!(Variant, 1, b = 2, c = 4),
+        E::Variant { a: 1, b: 2, c: 4 }
+    );
+    assert_eq!(E!(Other), E::Other { name: "other" });
+    assert_eq!(E!(Other, name = "x"), E::Other { name: "x" });
+    assert_eq!(E!(Variant, 1; c = 4), E::Variant { a: 1, b: 3, c: 4 });
+    // unit and tuple variants are left unchanged
+    assert_eq!(E::Unit, E::Unit);
+    assert_eq!(E::Tuple(1, 2), E::Tuple(1, 2));
+}
+
+#[test]
+fn shuffle_positional() {
+    opt_args! {
+        #[opt_args(shuffle, positional, non_export)]
+        #[derive(Debug, PartialEq)]
+        enum Shape {
+            Point { x: i32 = 1, y: i32? },
+            Rect { w: u8, h: u8 = 2, color: &'static str = "red" },
+        }
+    }
+
+    assert_eq!(Shape!(Point, y = 2, x = 3), Shape::Point { x: 3, y: 2 });
+    assert_eq!(Shape!(Point, ..), Shape::Point { x: 1, y: 0 });
+    assert_eq!(
+        Shape!(Rect, 1, color = "blue", h = 5),
+        Shape::Rect {
+            w: 1,
+            h: 5,
+            color: "blue"
+        }
+    );
+    assert_eq!(
+        Shape!(Rect, 1, 3),
+        Shape::Rect {
+            w: 1,
+            h: 3,
+            color: "red"
+        }
+    );
+}
+
+#[test]
+fn generic_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        enum Wrapper<T: Default> {
+            Full { value: T = T::default(), count: usize = 1 },
+        }
+    }
+
+    let result: Wrapper<u8> = Wrapper!(Full);
+    assert_eq!(result, Wrapper::Full { value: 0, count: 1 });
+    assert_eq!(
+        Wrapper!(Full, value = 5u8, count = 2),
+        Wrapper::Full { value: 5, count: 2 }
+    );
+}
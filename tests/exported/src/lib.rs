@@ -0,0 +1,41 @@
+//! Items with exported macros, used by the tests of `opt_args` to call the macros from another crate
+
+use opt_args::opt_args;
+
+opt_args! {
+    #[opt_args(item_path = crate)]
+    pub fn keyed(a: u8, b: u8 = 5, c: u8?) -> (u8, u8, u8) {
+        (a, b, c)
+    }
+}
+
+opt_args! {
+    #[opt_args(shuffle, item_path = crate)]
+    pub fn shuffled(a: u8, b: u8 = 5, c: u8?) -> (u8, u8, u8) {
+        (a, b, c)
+    }
+}
+
+opt_args! {
+    #[opt_args(item_path = crate)]
+    pub fn aliased(a: u8, b: u8 = 2, #[opt_args(alias = old_c)] c: u8 = 3) -> (u8, u8, u8) {
+        (a, b, c)
+    }
+}
+
+/// The same expression as the default value of `workers`, evaluated inside this crate,
+/// which is never compiled with `--cfg test` as a dependency
+pub fn default_threads() -> usize {
+    if cfg!(test) {
+        1
+    } else {
+        4
+    }
+}
+
+opt_args! {
+    #[opt_args(item_path = crate)]
+    pub fn workers(threads: usize = if cfg!(test) { 1 } else { 4 }) -> usize {
+        threads
+    }
+}
@@ -0,0 +1,33 @@
+#![forbid(unsafe_code)]
+
+use opt_args::opt_args;
+
+opt_args! {
+    #[opt_args(call_convention = builder_fn)]
+    fn builder(a: u8, b: u8 = 5) -> u8 {
+        a + b
+    }
+}
+
+#[test]
+fn forbid_unsafe_code() {
+    opt_args! {
+        #[opt_args(shuffle, positional, transparent_errors, presence_flags, non_export)]
+        fn f<const N: usize>(a: u8, b: [u8; N] = [0; N], c: u8?) -> (u8, [u8; N], u8, bool) {
+            (a, b, c, has_c)
+        }
+
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct S<T> {
+            a: T?,
+            b: u8 = 1,
+        }
+    }
+
+    let result: (u8, [u8; 2], u8, bool) = f!(1);
+    assert_eq!(result, (1, [0, 0], 0, false));
+    assert_eq!(f!(1, c = 2, b = [1]), (1, [1], 2, true));
+    assert_eq!(S!(a = 1u8), S { a: 1, b: 1 });
+    assert_eq!(builder::build(builder::new(1)), 6);
+}
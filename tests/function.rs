@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use opt_args::opt_args;
 
 #[test]
@@ -126,3 +128,1489 @@ fn ordered() {
     result = ordered_internal!(1, b = 1, c = 1);
     assert_eq!(result, (1, 1, 1));
 }
+
+#[test]
+fn result_and_question_mark() -> Result<(), std::num::ParseIntError> {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn parse_sum(a: &str, b: &str = "0") -> Result<i32, std::num::ParseIntError> {
+            Ok(a.parse::<i32>()? + b.parse::<i32>()?)
+        }
+    }
+
+    let result = parse_sum!("1")?;
+    assert_eq!(result, 1);
+    let result = parse_sum!("1", b = "2")?;
+    assert_eq!(result, 3);
+    assert!(parse_sum!("x").is_err());
+    assert!(parse_sum!("1", b = "y").is_err());
+    Ok(())
+}
+
+#[test]
+fn rename_all_macros() {
+    opt_args! {
+        #![opt_args(rename_all_macros = "build_{}_macro")]
+
+        #[opt_args(non_export)]
+        fn first(a: i32, b: i32 = 1) -> i32 {
+            a + b
+        }
+
+        #[opt_args(non_export)]
+        fn second(a: i32?) -> i32 {
+            a
+        }
+
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Third {
+            a: i32,
+            b: i32?,
+        }
+    }
+
+    assert_eq!(build_first_macro!(1), 2);
+    assert_eq!(build_second_macro!(a = 3), 3);
+    assert_eq!(build_Third_macro!(1), Third { a: 1, b: 0 });
+}
+
+mod inner {
+    pub mod nested {
+        use opt_args::opt_args;
+
+        opt_args! {
+            #[opt_args(non_export)]
+            pub(in crate::inner) fn restricted(a: i32, b: i32 = 2) -> i32 {
+                a * b
+            }
+        }
+
+        pub fn call_macro() -> i32 {
+            restricted!(3)
+        }
+    }
+
+    pub fn call_function() -> i32 {
+        nested::restricted(1, 5)
+    }
+}
+
+#[test]
+fn restricted_visibility() {
+    assert_eq!(inner::nested::call_macro(), 6);
+    assert_eq!(inner::call_function(), 5);
+}
+
+mod private {
+    use opt_args::opt_args;
+
+    opt_args! {
+        #[opt_args(item_path = crate::private)]
+        pub(crate) fn private_item(a: i32, b: i32 = 2) -> i32 {
+            a * b
+        }
+    }
+}
+
+#[test]
+fn item_path() {
+    assert_eq!(private_item!(3), 6);
+    assert_eq!(private_item!(3, b = 3), 9);
+}
+
+mod relative {
+    #[macro_use]
+    pub mod items {
+        use opt_args::opt_args;
+
+        opt_args! {
+            #[opt_args(item_path = super::items, non_export)]
+            pub fn relative_item(a: i32, b: i32 = 2) -> i32 {
+                a - b
+            }
+        }
+    }
+
+    pub mod callers {
+        pub fn call_macro() -> i32 {
+            relative_item!(5)
+        }
+    }
+}
+
+#[test]
+fn relative_item_path() {
+    assert_eq!(relative::callers::call_macro(), 3);
+}
+
+mod types {
+    pub mod kinds {
+        #[derive(Debug, PartialEq)]
+        pub enum Kind {
+            Default,
+            Other,
+        }
+    }
+
+    pub use kinds::Kind;
+}
+
+mod qualified {
+    #[macro_use]
+    pub mod items {
+        use opt_args::opt_args;
+
+        opt_args! {
+            #[opt_args(item_path = crate::qualified::items, non_export)]
+            pub fn classify(
+                a: i32,
+                kind: crate::types::Kind = crate::types::Kind::Default,
+            ) -> (i32, crate::types::Kind) {
+                (a, kind)
+            }
+        }
+    }
+
+    pub mod callers {
+        use crate::types::Kind;
+
+        pub fn call_macro() -> [(i32, Kind); 2] {
+            [classify!(1), classify!(2, kind = Kind::Other)]
+        }
+    }
+}
+
+#[test]
+fn qualified_path_default() {
+    use types::Kind;
+
+    assert_eq!(
+        qualified::callers::call_macro(),
+        [(1, Kind::Default), (2, Kind::Other)]
+    );
+}
+
+#[test]
+fn positional() {
+    opt_args! {
+        #[opt_args(positional, non_export)]
+        fn positional(a: i32, b: i32 = 1, c: i32 = 2, d: i32?) -> (i32, i32, i32, i32) {
+            (a, b, c, d)
+        }
+    }
+
+    assert_eq!(positional!(0), (0, 1, 2, 0));
+    assert_eq!(positional!(0, 10), (0, 10, 2, 0));
+    assert_eq!(positional!(0, 10, 20, 30), (0, 10, 20, 30));
+    // skip optionals with `..`
+    assert_eq!(positional!(0, .., 20), (0, 1, 20, 0));
+    assert_eq!(positional!(0, .., .., 30), (0, 1, 2, 30));
+    assert_eq!(positional!(0, 10, .., 30), (0, 10, 2, 30));
+    // positional optionals followed by named optionals
+    assert_eq!(positional!(0, 10, d = 30), (0, 10, 2, 30));
+    assert_eq!(positional!(0, .., c = 20), (0, 1, 20, 0));
+    // named optionals only
+    assert_eq!(positional!(0, c = 20), (0, 1, 20, 0));
+}
+
+#[test]
+fn positional_ranges() {
+    opt_args! {
+        #[opt_args(positional, non_export)]
+        fn ranges(a: std::ops::RangeFull = .., b: std::ops::RangeTo<u8> = ..1) -> (std::ops::RangeFull, std::ops::RangeTo<u8>) {
+            (a, b)
+        }
+    }
+
+    assert_eq!(ranges!(.., ..5), (.., ..5));
+    assert_eq!(ranges!(.., b = ..5), (.., ..5));
+    assert_eq!(ranges!(), (.., ..1));
+}
+
+#[test]
+fn suggest_default() {
+    opt_args! {
+        #[opt_args(suggest_default, non_export)]
+        fn not_redundant(a: u32 = 1, b: &str = "b", c: f64 = 0.5, d: bool = true, e: String = String::new()) -> (u32, &str, f64, bool, String) {
+            (a, b, c, d, e)
+        }
+    }
+
+    assert_eq!(not_redundant!(), (1, "b", 0.5, true, String::new()));
+}
+
+#[test]
+fn key_value_form() {
+    opt_args! {
+        #[opt_args(shuffle, non_export)]
+        fn key_value(a: i32, b: i32?, c: i32 = 3) -> (i32, i32, i32) {
+            (a, b, c)
+        }
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn no_required(a: i32?, b: i32?) -> (i32, i32) {
+            (a, b)
+        }
+    }
+
+    macro_rules! forward {
+        ($macro:ident!($($required:expr),*; $($key:ident = $value:expr),*)) => {
+            $macro!($($required),*; $($key = $value),*)
+        };
+    }
+
+    assert_eq!(forward!(key_value!(1;)), (1, 0, 3));
+    assert_eq!(forward!(key_value!(1; c = 5, b = 4)), (1, 4, 5));
+    assert_eq!(key_value!(1; b = 2,), (1, 2, 3));
+    assert_eq!(forward!(no_required!(;)), (0, 0));
+    assert_eq!(forward!(no_required!(; a = 1, b = 2)), (1, 2));
+}
+
+#[test]
+fn const_generic_array_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn padded<const N: usize>(a: u8, pad: [u8; N] = [0; N]) -> (u8, [u8; N]) {
+            (a, pad)
+        }
+    }
+
+    // `N` is inferred from the expected type when `pad` is omitted
+    let result: (u8, [u8; 4]) = padded!(1);
+    assert_eq!(result, (1, [0; 4]));
+    // or from the value of `pad` when it's passed
+    assert_eq!(padded!(1, pad = [1, 2]), (1, [1, 2]));
+    assert_eq!(padded!(1, pad = [7; 3]), (1, [7, 7, 7]));
+}
+
+#[test]
+fn const_generic_mask_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn select<const N: usize>(values: [u8; N], mask: [bool; N] = [true; N]) -> Vec<u8> {
+            values
+                .into_iter()
+                .zip(mask)
+                .filter_map(|(value, keep)| keep.then_some(value))
+                .collect()
+        }
+    }
+
+    // `N` is inferred from the required argument, so the default has the same length
+    assert_eq!(select!([1, 2, 3]), vec![1, 2, 3]);
+    assert_eq!(select!([1, 2, 3, 4, 5]), vec![1, 2, 3, 4, 5]);
+    assert_eq!(
+        select!([1, 2, 3, 4, 5], mask = [true, false, true, false, true]),
+        vec![1, 3, 5]
+    );
+}
+
+#[macro_use]
+mod prelude {
+    use std::collections::BTreeMap;
+
+    use opt_args::opt_args;
+
+    pub const DEFAULT_KEY: u8 = 1;
+
+    opt_args! {
+        #[opt_args(prelude(std::collections::BTreeMap, crate::prelude::DEFAULT_KEY))]
+        pub fn with_prelude(a: u8, map: BTreeMap<u8, u8> = BTreeMap::from([(DEFAULT_KEY, 2)])) -> (u8, Vec<(u8, u8)>) {
+            (a, map.into_iter().collect())
+        }
+    }
+}
+
+#[test]
+fn prelude() {
+    use prelude::with_prelude;
+
+    assert_eq!(with_prelude!(0), (0, vec![(1, 2)]));
+    assert_eq!(
+        with_prelude!(0, map = [(3, 4)].into_iter().collect()),
+        (0, vec![(3, 4)])
+    );
+}
+
+#[test]
+fn lifetime_only_in_optionals() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn note<'a>(note: &'a str = "") -> &'a str {
+            note
+        }
+    }
+
+    // `'a` is inferred as `'static` from the default value
+    let result: &'static str = note!();
+    assert_eq!(result, "");
+    // `'a` is tied to the borrowed value
+    let owned = String::from("borrowed");
+    let result = note!(note = &owned);
+    assert_eq!(result, "borrowed");
+}
+
+#[test]
+fn doc_hidden() {
+    opt_args! {
+        #[opt_args(doc_hidden, non_export)]
+        fn hidden(a: i32?) -> i32 {
+            a
+        }
+    }
+
+    assert_eq!(hidden!(), 0);
+    assert_eq!(hidden!(a = 1), 1);
+}
+
+#[test]
+fn cfg_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn threads(
+            threads: usize = if cfg!(test) { 1 } else { 4 },
+            checks: bool = cfg!(not(test)),
+        ) -> (usize, bool) {
+            (threads, checks)
+        }
+    }
+
+    // tests are always compiled with `--cfg test`
+    assert_eq!(threads!(), (1, false));
+    assert_eq!(threads!(threads = 2), (2, false));
+}
+
+#[test]
+fn transparent_errors() {
+    opt_args! {
+        #[opt_args(transparent_errors, positional, non_export)]
+        fn f<T: Into<u32>>(a: u8, b: T?, c: &str = "c", d: u8?) -> (u8, u32, String, u8) {
+            (a, b.into(), c.to_string(), d)
+        }
+    }
+
+    let a = 1;
+    assert_eq!(f!(a, 2u16), (1, 2, "c".to_string(), 0));
+    assert_eq!(f!(a, 2u8, .., a), (1, 2, "c".to_string(), 1));
+    assert_eq!(f!(2, 3u32, c = "a", d = a), (2, 3, "a".to_string(), 1));
+}
+
+#[test]
+#[should_panic(expected = "no value")]
+fn never_type() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn fail(message: &str = "failed") -> ! {
+            panic!("{}", message)
+        }
+    }
+
+    let some: u8 = match Some(1) {
+        Some(value) => value,
+        None => fail!(),
+    };
+    assert_eq!(some, 1);
+    let _none: u8 = match None {
+        Some(value) => value,
+        None => fail!(message = "no value"),
+    };
+}
+
+#[test]
+fn block_default() {
+    fn some_option() -> Option<u32> {
+        None
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u32 = { some_option().unwrap_or(0) }, b: u32 = {
+            let base: u32 = 2;
+            let exponent = some_option().unwrap_or(3);
+            base.pow(exponent)
+        }) -> (u32, u32) {
+            (a, b)
+        }
+    }
+
+    assert_eq!(f!(), (0, 8));
+    assert_eq!(
+        f!(b = {
+            let half = 1;
+            half + half
+        }),
+        (0, 2)
+    );
+    assert_eq!(f!(a = 5), (5, 8));
+}
+
+// the builder functions call the function through `super`, so it must be declared inside a module
+opt_args! {
+    #[opt_args(call_convention = builder_fn)]
+    fn builder<'a, T: Default>(a: &'a str, b: u8 = 5, c: Vec<T>?) -> (&'a str, u8, Vec<T>, T) {
+        (a, b, c, T::default())
+    }
+
+    #[opt_args(call_convention = builder_fn, rename = async_builder)]
+    async fn builder_async(a: u8?) -> u8 {
+        a
+    }
+}
+
+#[test]
+fn builder_fn() {
+    assert_eq!(
+        builder::build(builder::new("a")),
+        ("a", 5, Vec::<u8>::new(), 0)
+    );
+    assert_eq!(
+        builder::build(builder::with_c(
+            builder::with_b(builder::new("a"), 1),
+            vec![true]
+        )),
+        ("a", 1, vec![true], false)
+    );
+    let partial = builder::with_c(builder::new("b"), vec![1]);
+    assert_eq!(
+        builder::build(builder::with_b(partial, 2)),
+        ("b", 2, vec![1], 0)
+    );
+    // the future is only created, to check that `build` is async as well
+    drop(async_builder::build(async_builder::with_a(
+        async_builder::new(),
+        1,
+    )));
+}
+
+#[test]
+fn const_generic_argument() {
+    opt_args! {
+        #[opt_args(non_export)]
+        const fn size(base: usize, extra: usize = 2, factor: usize = 1) -> usize {
+            (base + extra) * factor
+        }
+    }
+
+    struct Buffer<const N: usize>([u8; N]);
+
+    impl<const N: usize> Buffer<N> {
+        fn len(&self) -> usize {
+            N
+        }
+    }
+
+    let buffer = Buffer::<{ size!(1) }>([0; size!(1)]);
+    assert_eq!(buffer.len(), 3);
+    let buffer = Buffer::<{ size!(1, factor = 2) }>([0; 6]);
+    assert_eq!(buffer.0.len(), 6);
+    const SIZE: usize = size!(2, extra = 0);
+    assert_eq!(SIZE, 2);
+}
+
+#[test]
+#[deny(unused)]
+fn argument_attributes() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u8, #[allow(unused_variables)] b: u8 = 5, #[allow(unused_variables)] c: u8?) -> u8 {
+            a
+        }
+    }
+
+    assert_eq!(f!(1, c = 3), 1);
+}
+
+#[test]
+fn named_defaults() {
+    fn expensive() -> Vec<u8> {
+        vec![1, 2, 3]
+    }
+
+    opt_args! {
+        #![opt_args(defaults(data = expensive(), empty = Vec::new()))]
+
+        #[opt_args(non_export)]
+        fn f(a: Vec<u8> = @data, b: Vec<u8> = @data, c: Vec<u8> = @empty) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(f!(), (vec![1, 2, 3], vec![1, 2, 3], vec![]));
+    assert_eq!(f!(b = vec![4]), (vec![1, 2, 3], vec![4], vec![]));
+}
+
+#[test]
+fn labeled_calls() {
+    opt_args! {
+        #[opt_args(labeled_calls, transparent_errors, non_export)]
+        fn f<T: Default>(a: u8, b: u8 = 5, c: T?) -> (u8, u8, T) {
+            (a, b, c)
+        }
+    }
+
+    let b = 0;
+    assert_eq!(f!(1), (1, 5, 0u8));
+    assert_eq!(f!(1, b = b, c = 2u16), (1, 0, 2));
+}
+
+#[test]
+fn size_of_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn layout<T>(
+            value: T,
+            size: usize = std::mem::size_of::<T>(),
+            align: usize = std::mem::align_of::<T>(),
+        ) -> (T, usize, usize) {
+            (value, size, align)
+        }
+    }
+
+    assert_eq!(layout!(1u8), (1, 1, 1));
+    assert_eq!(layout!(1u64, align = 1), (1, 8, 1));
+    assert_eq!(layout!([0u32; 4]), ([0; 4], 16, 4));
+}
+
+#[test]
+fn extern_rust_abi() {
+    opt_args! {
+        #[opt_args(non_export)]
+        extern "Rust" fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+            a + b + c
+        }
+    }
+
+    let _: extern "Rust" fn(u8, u8, u8) -> u8 = f;
+    assert_eq!(f!(1), 1 + 5);
+    assert_eq!(f!(1, c = 3), 1 + 5 + 3);
+}
+
+#[test]
+fn presence_flags() {
+    opt_args! {
+        #[opt_args(presence_flags, positional, non_export)]
+        fn f<T: Default>(a: u8, b: T?, c: u8 = 5, d: usize = std::mem::size_of::<T>()) -> (u8, Option<T>, u8, bool) {
+            let b = if has_b { Some(b) } else { None };
+            // an explicit value equal to the default is still marked as provided
+            let c = if has_c && c == 5 { 0 } else { c };
+            (a, b, c, has_d && d > 0)
+        }
+    }
+
+    assert_eq!(f!(1), (1, None::<u8>, 5, false));
+    assert_eq!(f!(1, 0u8), (1, Some(0), 5, false));
+    assert_eq!(f!(1, .., 5), (1, None::<u8>, 0, false));
+    assert_eq!(f!(1, c = 5, d = 1), (1, None::<u8>, 0, true));
+    assert_eq!(f(1, 2u8, 3, 0, true, true, false), (1, Some(2), 3, false));
+}
+
+#[test]
+#[allow(non_local_definitions)]
+fn local_scope() {
+    opt_args! {
+        fn local_exported(a: u8 = 1) -> u8 {
+            a
+        }
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn local_non_exported(a: u8 = 2) -> u8 {
+            a
+        }
+    }
+
+    fn nested() -> u8 {
+        opt_args! {
+            fn nested_exported(a: u8 = 3) -> u8 {
+                a
+            }
+        }
+
+        nested_exported!()
+    }
+
+    assert_eq!(local_exported!(), 1);
+    assert_eq!(local_non_exported!(a = 3), 3);
+    assert_eq!(nested(), 3);
+}
+
+#[test]
+fn macro_case() {
+    opt_args! {
+        #[opt_args(macro_case = pascal, non_export)]
+        fn new_point(x: i32?, y: i32?) -> (i32, i32) {
+            (x, y)
+        }
+
+        #[opt_args(macro_case = snake, non_export)]
+        #[allow(non_snake_case)]
+        fn MakePair(a: u8 = 1) -> (u8, u8) {
+            (a, a)
+        }
+
+        #[opt_args(macro_case = keep, non_export)]
+        #[allow(non_snake_case)]
+        fn keep_Name(a: u8?) -> u8 {
+            a
+        }
+    }
+
+    assert_eq!(NewPoint!(y = 1), (0, 1));
+    assert_eq!(make_pair!(), (1, 1));
+    assert_eq!(keep_Name!(a = 2), 2);
+}
+
+#[test]
+fn match_default() {
+    #[derive(Debug, PartialEq)]
+    enum Mode {
+        Debug,
+        Release,
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(
+            mode: Mode = match cfg!(debug_assertions) {
+                true => Mode::Debug,
+                false => Mode::Release,
+            },
+            level: u8 = if let Some(level) = option_env!("OPT_ARGS_TEST_LEVEL") {
+                level.len() as u8
+            } else {
+                3
+            },
+        ) -> (Mode, u8) {
+            (mode, level)
+        }
+    }
+
+    let expected = if cfg!(debug_assertions) {
+        Mode::Debug
+    } else {
+        Mode::Release
+    };
+    let expected_level = option_env!("OPT_ARGS_TEST_LEVEL").map_or(3, |level| level.len() as u8);
+    assert_eq!(f!(), (expected, expected_level));
+    assert_eq!(f!(mode = Mode::Release, level = 1), (Mode::Release, 1));
+}
+
+#[test]
+fn min_args() {
+    opt_args! {
+        #[opt_args(min_args = 2, positional, non_export)]
+        fn f(a: u8, b: u8?, c: u8?, d: u8?) -> (u8, u8, u8, u8) {
+            (a, b, c, d)
+        }
+    }
+
+    assert_eq!(f!(1, b = 2, c = 3), (1, 2, 3, 0));
+    assert_eq!(f!(1, 2, d = 4), (1, 2, 0, 4));
+    assert_eq!(f!(1, .., 3, 4), (1, 0, 3, 4));
+    assert_eq!(f!(1, b = 2, c = 3, d = 4), (1, 2, 3, 4));
+}
+
+#[test]
+fn error_message() {
+    opt_args! {
+        #[opt_args(error_message = "use `f_builder` instead", non_export)]
+        fn f(a: u8, b: u8 = 5) -> u8 {
+            a + b
+        }
+    }
+
+    assert_eq!(f!(1, b = 1), 2);
+}
+
+#[test]
+fn previous_argument_defaults() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn range(start: u32, len: u32 = 10, end: u32 = start + len) -> (u32, u32, u32) {
+            (start, len, end)
+        }
+    }
+
+    assert_eq!(range!(1), (1, 10, 11));
+    assert_eq!(range!(1, len = 2), (1, 2, 3));
+    assert_eq!(range!(1, end = 5), (1, 10, 5));
+}
+
+#[test]
+fn generic_const_fn_default() {
+    const fn compute_cap<T>() -> usize {
+        64 / std::mem::size_of::<T>()
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn buffer<T: Default + Clone>(value: T, cap: usize = compute_cap::<T>()) -> Vec<T> {
+            vec![value; cap]
+        }
+    }
+
+    assert_eq!(buffer!(1u8).len(), 64);
+    assert_eq!(buffer!(1u32).len(), 16);
+    assert_eq!(buffer!(1u64, cap = 2), vec![1, 1]);
+}
+
+#[test]
+fn require_all_named() {
+    opt_args! {
+        #[opt_args(positional, require_all_named, non_export)]
+        fn f(a: u8, b: u8 = 5, c: u8?) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(f!(1), (1, 5, 0));
+    assert_eq!(f!(1, c = 3), (1, 5, 3));
+    assert_eq!(f!(1, b = 2, c = 3), (1, 2, 3));
+}
+
+#[test]
+fn shared_lifetime() {
+    #[derive(Debug, PartialEq)]
+    struct Node(&'static str);
+
+    static ROOT: Node = Node("root");
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn link<'a>(parent: &'a Node, child: &'a Node = &ROOT) -> (&'a str, &'a str) {
+            (parent.0, child.0)
+        }
+    }
+
+    let parent = Node("parent");
+    assert_eq!(link!(&parent), ("parent", "root"));
+    let child = Node("child");
+    assert_eq!(link!(&parent, child = &child), ("parent", "child"));
+    // the static default doesn't extend the lifetime required for `parent`
+    let root = {
+        let local = Node("local");
+        link!(&local).1.len()
+    };
+    assert_eq!(root, 4);
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated() {
+    opt_args! {
+        #[opt_args(deprecated = "use `g!` instead", non_export)]
+        fn f(a: u8, b: u8 = 5) -> u8 {
+            a + b
+        }
+    }
+
+    assert_eq!(f!(1), 6);
+    assert_eq!(f!(1, b = 1), 2);
+}
+
+#[test]
+fn boxed_error_default() {
+    use std::error::Error;
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn fail(message: &str, cause: Option<Box<dyn Error + Send + Sync>> = None) -> String {
+            match cause {
+                Some(cause) => format!("{}: {}", message, cause),
+                None => message.to_string(),
+            }
+        }
+    }
+
+    assert_eq!(fail!("failed"), "failed");
+    let error = "x".parse::<u8>().unwrap_err();
+    assert_eq!(
+        fail!("failed", cause = Some(Box::new(error))),
+        "failed: invalid digit found in string"
+    );
+}
+
+#[test]
+fn method_chain_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u8, level: u16 = " 1024,".trim().trim_end_matches(',').parse().unwrap()) -> u16 {
+            a as u16 + level
+        }
+    }
+
+    assert_eq!(f!(1), 1025);
+    assert_eq!(f!(1, level = "7".parse().unwrap()), 8);
+}
+
+#[test]
+#[allow(clippy::too_many_arguments)]
+fn shuffle_many() {
+    opt_args! {
+        #[opt_args(shuffle, positional, non_export)]
+        fn f(a: u8?, b: u8?, c: u8?, d: u8?, e: u8?, f: u8?, g: u8?, h: u8?) -> [u8; 8] {
+            [a, b, c, d, e, f, g, h]
+        }
+    }
+
+    assert_eq!(f!(), [0; 8]);
+    assert_eq!(
+        f!(h = 8, g = 7, f = 6, e = 5, d = 4, c = 3, b = 2, a = 1),
+        [1, 2, 3, 4, 5, 6, 7, 8]
+    );
+    assert_eq!(f!(e = 5, b = 2, h = 8), [0, 2, 0, 0, 5, 0, 0, 8]);
+    assert_eq!(f!(1, .., d = 4, c = 3), [1, 0, 3, 4, 0, 0, 0, 0]);
+    assert_eq!(f!(; h = 8, a = 1), [1, 0, 0, 0, 0, 0, 0, 8]);
+}
+
+#[test]
+fn prefix_suffix() {
+    opt_args! {
+        #[opt_args(non_export, prefix = "make_")]
+        #[derive(Debug, PartialEq)]
+        struct Widget {
+            a: i32?,
+        }
+
+        #[opt_args(non_export, prefix = "call_", suffix = "_now")]
+        fn widget(a: i32?) -> i32 {
+            a
+        }
+
+        #[opt_args(non_export, prefix = "unused_", rename = renamed_widget)]
+        fn other_widget(a: i32?) -> i32 {
+            a
+        }
+    }
+
+    assert_eq!(make_Widget!(a = 1), Widget { a: 1 });
+    assert_eq!(call_widget_now!(a = 2), 2);
+    assert_eq!(renamed_widget!(), 0);
+}
+
+#[test]
+fn lifetime_bound_closure() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn run<'a, F: FnOnce(&'a str)>(cb: F, prefix: &'a str = "") {
+            cb(prefix)
+        }
+    }
+
+    run!(|s| println!("{s}"));
+    run!(|_s| {}, prefix = "x");
+
+    let mut seen = vec![];
+    run!(|s| seen.push(s.to_string()));
+    run!(|s| seen.push(s.to_string()), prefix = "x");
+    assert_eq!(seen, ["", "x"]);
+}
+
+#[test]
+fn thread_local_default() {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CURRENT: RefCell<String> = RefCell::new(String::from("main"));
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn context(ctx: String = CURRENT.with(|c| c.borrow().clone())) -> String {
+            ctx
+        }
+    }
+
+    assert_eq!(context!(), "main");
+    CURRENT.with(|c| *c.borrow_mut() = String::from("changed"));
+    assert_eq!(context!(), "changed");
+    assert_eq!(context!(ctx = String::from("explicit")), "explicit");
+    // the default value is evaluated in the thread calling the macro
+    let spawned = std::thread::spawn(|| context!()).join().unwrap();
+    assert_eq!(spawned, "main");
+}
+
+#[test]
+fn const_generics_defaults() {
+    opt_args! {
+        #[opt_args(non_export, shuffle, const_generics_defaults(N = 2, M = 1))]
+        fn repeat<T: Copy, const N: usize, const M: usize>(value: T, fill: T?) -> ([T; N], [T; M]) {
+            let _ = fill;
+            ([value; N], [value; M])
+        }
+    }
+
+    assert_eq!(repeat!(1u8), ([1, 1], [1]));
+    assert_eq!(repeat!(1u8, N = 3), ([1, 1, 1], [1]));
+    assert_eq!(repeat!(1u8, fill = 0, M = 2), ([1, 1], [1, 1]));
+    assert_eq!(repeat!(1u8, M = 0, N = 1 + 1), ([1, 1], []));
+    assert_eq!(repeat!(1u8; N = 4), ([1; 4], [1]));
+}
+
+#[test]
+fn negative_and_float_defaults() {
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f(offset: i32 = -1, ratio: f64 = -0.5, big: f64 = 1e10, small: f32 = -2.5e-3) -> (i32, f64, f64, f32) {
+            (offset, ratio, big, small)
+        }
+    }
+
+    assert_eq!(f!(), (-1, -0.5, 1e10, -2.5e-3));
+    assert_eq!(f!(ratio = -1.0), (-1, -1.0, 1e10, -2.5e-3));
+    assert_eq!(f!(small = 1e3, offset = -2), (-2, -0.5, 1e10, 1000.0));
+}
+
+#[test]
+fn skip() {
+    #[derive(Debug, PartialEq)]
+    struct Context(u8);
+
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f(
+            a: u8,
+            b: u8 = 1,
+            #[opt_args(skip)] ctx: &Context?,
+            c: u8 = ctx.0 + b,
+            #[opt_args(skip)] d: u8,
+        ) -> (u8, u8, u8, u8, u8) {
+            (a, b, ctx.0, c, d)
+        }
+    }
+
+    let ctx = Context(10);
+    assert_eq!(f!(0, &ctx, 4), (0, 1, 10, 11, 4));
+    assert_eq!(f!(0, &ctx, 4, c = 3, b = 2), (0, 2, 10, 3, 4));
+}
+
+#[test]
+fn alias() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u8, b: u8 = 2, #[opt_args(alias = old_c)] c: u8 = 3) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(f!(1, c = 4), (1, 2, 4));
+    assert_eq!(f!(1, old_c = 4), (1, 2, 4));
+    assert_eq!(f!(1, b = 5, old_c = 4), (1, 5, 4));
+    assert_eq!(f!(1; old_c = 4), (1, 2, 4));
+
+    opt_args! {
+        #[opt_args(non_export, shuffle, error_message = "Wrong arguments")]
+        fn g(a: u8, #[opt_args(alias = old_b)] b: u8 = 2, c: u8 = 3) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(g!(1, c = 4, old_b = 5), (1, 5, 4));
+    assert_eq!(g!(1, b = 5), (1, 5, 3));
+}
+
+#[test]
+fn into_default() {
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f(a: u8, name: String = "default".into(), path: std::path::PathBuf = "/tmp".into()) -> (u8, String, std::path::PathBuf) {
+            (a, name, path)
+        }
+    }
+
+    assert_eq!(f!(1), (1, "default".to_string(), "/tmp".into()));
+    assert_eq!(
+        f!(1, name = "other".into()),
+        (1, "other".to_string(), "/tmp".into())
+    );
+    assert_eq!(
+        f!(1, path = "/home".into(), name = String::from("other")),
+        (1, "other".to_string(), "/home".into())
+    );
+}
+
+#[test]
+fn vec_repeat_default() {
+    const N: usize = 1024;
+
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f(a: u8, buf: Vec<u8> = vec![0; N], fill: [u8; 2] = [1; 2]) -> (u8, Vec<u8>, [u8; 2]) {
+            (a, buf, fill)
+        }
+    }
+
+    let (a, buf, fill) = f!(1);
+    assert_eq!((a, buf.len(), fill), (1, N, [1, 1]));
+    assert!(buf.iter().all(|byte| *byte == 0));
+    let (_, buf, fill) = f!(1, fill = [2; 2], buf = vec![3; 4]);
+    assert_eq!((buf, fill), (vec![3; 4], [2, 2]));
+}
+
+#[test]
+fn associated_type_binding() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u8, it: impl Iterator<Item = u8> = std::iter::empty()) -> u8 {
+            a + it.sum::<u8>()
+        }
+    }
+
+    assert_eq!(f!(1), 1);
+    assert_eq!(f!(1, it = [2, 3].into_iter()), 6);
+    assert_eq!(f!(1, it = std::iter::once(4)), 5);
+}
+
+#[test]
+fn impl_future() {
+    use std::{
+        cell::RefCell,
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        fn spawn(log: &RefCell<Vec<u64>>, delay: u64 = 0) -> impl Future<Output = ()> + '_ {
+            async move { log.borrow_mut().push(delay) }
+        }
+    }
+
+    let log = RefCell::new(vec![]);
+    let mut future = pin!(async {
+        spawn!(&log).await;
+        spawn!(&log, delay = 5).await;
+    });
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+    assert_eq!(poll, Poll::Ready(()));
+    assert_eq!(*log.borrow(), [0, 5]);
+}
+
+#[test]
+fn question_mark_default() -> Result<(), std::num::ParseIntError> {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn add(a: i32, b: i32 = "2".parse::<i32>()?) -> i32 {
+            a + b
+        }
+    }
+
+    assert_eq!(add!(1), 3);
+    assert_eq!(add!(1, b = 5), 6);
+    Ok(())
+}
+
+#[test]
+fn await_default() {
+    use std::{
+        future::{ready, Future},
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        async fn add(a: u8, b: u8 = ready(2).await) -> u8 {
+            a + b
+        }
+    }
+
+    let mut future = pin!(async { (add!(1).await, add!(1, b = 5).await) });
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+    assert_eq!(poll, Poll::Ready((3, 6)));
+}
+
+#[test]
+fn const_generic_param() {
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn make<const N: usize>(a: [u8; N], b: u8 = 0, c: [u8; N] = [1; N]) -> ([u8; N], u8, [u8; N]) {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(make!([1, 2, 3]), ([1, 2, 3], 0, [1, 1, 1]));
+    assert_eq!(make!([1, 2], c = [3, 4], b = 5), ([1, 2], 5, [3, 4]));
+}
+
+#[test]
+fn bitflags_default() {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Flags(u8);
+
+    impl Flags {
+        const A: Flags = Flags(0b001);
+        const B: Flags = Flags(0b010);
+        const C: Flags = Flags(0b100);
+
+        const fn empty() -> Flags {
+            Flags(0)
+        }
+    }
+
+    impl std::ops::BitOr for Flags {
+        type Output = Flags;
+
+        fn bitor(self, other: Flags) -> Flags {
+            Flags(self.0 | other.0)
+        }
+    }
+
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f(a: u8, flags: Flags = Flags::A | Flags::B, extra: Flags = Flags::empty()) -> (u8, Flags, Flags) {
+            (a, flags, extra)
+        }
+    }
+
+    assert_eq!(f!(1), (1, Flags(0b011), Flags(0)));
+    assert_eq!(
+        f!(1, extra = Flags::C | Flags::A),
+        (1, Flags(0b011), Flags(0b101))
+    );
+    assert_eq!(
+        f!(1, flags = Flags::C, extra = Flags::B),
+        (1, Flags(0b100), Flags(0b010))
+    );
+}
+
+#[test]
+fn snapshot_arms() {
+    opt_args! {
+        #[opt_args(non_export, snapshot_arms)]
+        fn f(a: u8, b: u8 = 1, c: u8?) -> u8 {
+            a + b + c
+        }
+    }
+
+    assert_eq!(
+        F_ARMS,
+        "f!(a)\nf!(a, b = ..)\nf!(a, c = ..)\nf!(a, b = .., c = ..)"
+    );
+    assert_eq!(f!(1, c = 2), 4);
+}
+
+#[test]
+fn multiple_items() {
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn first(a: u8, b: u8 = 1, c: u8?) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+
+        #[opt_args(non_export)]
+        fn second(a: u8?, b: u8 = 2) -> (u8, u8) {
+            (a, b)
+        }
+
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Third {
+            a: u8 = 3,
+        }
+    }
+
+    assert_eq!(first!(0, c = 5, b = 4), (0, 4, 5));
+    assert_eq!(second!(b = 7), (0, 7));
+    assert_eq!(second!(a = 1, b = 7), (1, 7));
+    assert_eq!(Third!(), Third { a: 3 });
+}
+
+#[test]
+fn keyword_required() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u8, b: u8 = 1, #[opt_args(required)] c: u8) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+
+        #[opt_args(non_export, shuffle)]
+        fn g(a: u8, #[opt_args(required)] b: u8, c: u8 = 3, d: u8?) -> (u8, u8, u8, u8) {
+            (a, b, c, d)
+        }
+
+        #[opt_args(non_export, positional)]
+        fn h(a: u8, b: u8 = 1, #[opt_args(required)] c: u8, d: u8 = 4) -> (u8, u8, u8, u8) {
+            (a, b, c, d)
+        }
+    }
+
+    assert_eq!(f!(0, c = 2), (0, 1, 2));
+    assert_eq!(f!(0, b = 3, c = 2), (0, 3, 2));
+    assert_eq!(g!(0, b = 2), (0, 2, 3, 0));
+    assert_eq!(g!(0, d = 5, b = 2, c = 4), (0, 2, 4, 5));
+    assert_eq!(h!(0, 5, c = 2), (0, 5, 2, 4));
+    assert_eq!(h!(0, c = 2, d = 6), (0, 1, 2, 6));
+}
+
+#[test]
+fn fn_wrapper() {
+    opt_args! {
+        #[opt_args(non_export, fn_wrapper)]
+        fn f(a: u8, b: u8 = 2, c: u8?) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+
+        #[opt_args(non_export, fn_wrapper)]
+        fn keyword_arg(a: u8, b: u8 = 2, #[opt_args(required)] c: u8) -> (u8, u8, u8) {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(
+        f_with(
+            1,
+            FArgs {
+                b: 3,
+                ..Default::default()
+            }
+        ),
+        f!(1, b = 3)
+    );
+    assert_eq!(f_with(1, FArgs::default()), (1, 2, 0));
+    assert_eq!(f_with(1, FArgs { b: 3, c: 4 }), (1, 3, 4));
+    assert_eq!(
+        keyword_arg_with(1, 5, KeywordArgArgs::default()),
+        keyword_arg!(1, c = 5)
+    );
+}
+
+#[test]
+fn default_of_optional_dependency() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(a: u8 = 1, b: u8 = a * 2) -> (u8, u8) {
+            (a, b)
+        }
+
+        #[opt_args(non_export, shuffle)]
+        fn g(a: u8 = 1, b: u8 = a * 2) -> (u8, u8) {
+            (a, b)
+        }
+
+        #[opt_args(non_export, positional)]
+        fn h(a: u8 = 1, b: u8 = a * 2) -> (u8, u8) {
+            (a, b)
+        }
+    }
+
+    assert_eq!(f!(), (1, 2));
+    assert_eq!(f!(a = 3), (3, 6));
+    assert_eq!(f!(b = 5), (1, 5));
+    assert_eq!(f!(a = 3, b = 5), (3, 5));
+    assert_eq!(g!(), (1, 2));
+    assert_eq!(g!(a = 3), (3, 6));
+    assert_eq!(g!(b = 5), (1, 5));
+    assert_eq!(g!(b = 5, a = 3), (3, 5));
+    assert_eq!(h!(), (1, 2));
+    assert_eq!(h!(3), (3, 6));
+    assert_eq!(h!(b = 5), (1, 5));
+    assert_eq!(h!(3, 5), (3, 5));
+}
+
+#[test]
+fn where_clause() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f<T>(a: T, b: T = a.clone(), c: u8?) -> (T, T, u8)
+        where
+            T: Clone + Send,
+        {
+            (a, b, c)
+        }
+
+        #[opt_args(non_export, shuffle)]
+        fn g<T>(a: T, b: Vec<T> = Vec::new(), c: u8 = 1) -> (T, Vec<T>, u8)
+        where
+            T: Clone,
+        {
+            (a, b, c)
+        }
+    }
+
+    assert_eq!(f!(1), (1, 1, 0));
+    assert_eq!(f!(1, b = 2, c = 3), (1, 2, 3));
+    assert_eq!(g!(1, c = 2, b = vec![3]), (1, vec![3], 2));
+}
+
+const DATA: [u8; 3] = [4, 5, 6];
+
+#[test]
+fn array_index_default() {
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f(first: u8 = DATA[0], last: u8 = DATA[DATA.len() - 1]) -> (u8, u8) {
+            (first, last)
+        }
+    }
+
+    assert_eq!(f!(), (4, 6));
+    assert_eq!(f!(first = 1), (1, 6));
+    assert_eq!(f!(last = DATA[1], first = 1), (1, 5));
+}
+
+#[test]
+fn keyword_optional() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn f(#[opt_args(keyword)] verbose: bool = false, a: u8, b: u8 = 2) -> (bool, u8, u8) {
+            (verbose, a, b)
+        }
+
+        #[opt_args(non_export, shuffle)]
+        fn g(#[opt_args(keyword)] verbose: bool?, a: u8, b: u8 = 2, c: u8?) -> (bool, u8, u8, u8) {
+            (verbose, a, b, c)
+        }
+
+        #[opt_args(non_export, positional)]
+        fn h(a: u8, #[opt_args(keyword)] verbose: bool?, b: u8 = 2, c: u8 = b + 1) -> (u8, bool, u8, u8) {
+            (a, verbose, b, c)
+        }
+    }
+
+    assert_eq!(f!(1), (false, 1, 2));
+    assert_eq!(f!(1, verbose = true), (true, 1, 2));
+    assert_eq!(f!(1, verbose = true, b = 3), (true, 1, 3));
+    assert_eq!(g!(1, c = 4, verbose = true), (true, 1, 2, 4));
+    assert_eq!(h!(1, 3), (1, false, 3, 4));
+    assert_eq!(h!(1, 3, 5, verbose = true), (1, true, 3, 5));
+    assert_eq!(h!(1, .., verbose = true), (1, true, 2, 3));
+}
+
+static LAZY_CALLS: AtomicUsize = AtomicUsize::new(0);
+static CACHED_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn next_lazy() -> usize {
+    LAZY_CALLS.fetch_add(1, Ordering::SeqCst)
+}
+
+fn next_cached() -> usize {
+    CACHED_CALLS.fetch_add(1, Ordering::SeqCst)
+}
+
+opt_args! {
+    #[opt_args(non_export)]
+    fn counters(
+        #[opt_args(lazy)] lazy: usize = next_lazy(),
+        #[opt_args(cached)] cached: usize = next_cached(),
+    ) -> (usize, usize) {
+        (lazy, cached)
+    }
+
+    mod nested {
+        use super::next_cached;
+
+        #[opt_args(non_export)]
+        pub struct Cached {
+            #[opt_args(cached)]
+            pub value: usize = next_cached() + 100,
+        }
+    }
+}
+
+#[test]
+fn cached_default() {
+    assert_eq!(counters!(), (0, 0));
+    assert_eq!(counters!(), (1, 0));
+    assert_eq!(counters!(cached = 5), (2, 5));
+    assert_eq!(counters!(lazy = 7), (7, 0));
+    assert_eq!(LAZY_CALLS.load(Ordering::SeqCst), 3);
+    assert_eq!(CACHED_CALLS.load(Ordering::SeqCst), 1);
+    assert_eq!(Cached!().value, 101);
+    assert_eq!(Cached!().value, 101);
+    assert_eq!(CACHED_CALLS.load(Ordering::SeqCst), 2);
+}
+
+trait HasMax {
+    const MAX: u64;
+}
+
+struct Small;
+
+struct Large;
+
+impl HasMax for Small {
+    const MAX: u64 = 10;
+}
+
+impl HasMax for Large {
+    const MAX: u64 = 1000;
+}
+
+#[test]
+fn qualified_associated_const_default() {
+    opt_args! {
+        #[opt_args(non_export, shuffle)]
+        fn f<T: HasMax>(_kind: T, limit: u64 = <T as HasMax>::MAX, min: u64 = <T>::MAX / 10) -> (u64, u64) {
+            (limit, min)
+        }
+    }
+
+    assert_eq!(f!(Small), (10, 1));
+    assert_eq!(f!(Large), (1000, 100));
+    assert_eq!(f!(Large, limit = 5), (5, 100));
+    assert_eq!(f!(Small, min = 0, limit = 5), (5, 0));
+}
+
+#[test]
+fn try_defaults() -> Result<(), std::num::ParseIntError> {
+    opt_args! {
+        #[opt_args(non_export, try)]
+        fn scale(
+            value: &str,
+            factor: i32 = "2".parse::<i32>(),
+            offset: i32 = "x".parse::<i32>(),
+            shift: u32?,
+        ) -> Result<i32, std::num::ParseIntError> {
+            Ok((value.parse::<i32>()? * factor + offset) << shift)
+        }
+    }
+
+    fn scale_all() -> Result<i32, std::num::ParseIntError> {
+        let scaled = scale!("3", offset = 1)?;
+        Ok(scaled + scale!("1")?)
+    }
+
+    assert_eq!(scale!("3", offset = 1)?, 7);
+    assert_eq!(scale!("3", factor = 3, offset = 0, shift = 1)?, 18);
+    assert!(scale!("y", offset = 0).is_err());
+    assert!(scale_all().is_err());
+    Ok(())
+}
+
+#[test]
+fn builtin_macro_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        fn tag(
+            name: &'static str,
+            id: &'static str = concat!(module_path!(), "::", stringify!(default)),
+        ) -> (&'static str, &'static str) {
+            (name, id)
+        }
+    }
+
+    assert_eq!(tag!("a"), ("a", "function::default"));
+    assert_eq!(tag!("a", id = "b"), ("a", "b"));
+}
@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+use opt_args::opt_args;
+
+#[derive(Debug, PartialEq)]
+struct Foo {
+    a: i32,
+    b: u8,
+}
+
+opt_args! {
+    impl Foo {
+        #[opt_args(non_export)]
+        fn new(a: i32, b: u8 = 1) -> Self {
+            Self { a, b }
+        }
+
+        #[opt_args(non_export, shuffle)]
+        pub fn with_sum(a: i32?, b: u8 = Self::DEFAULT_B) -> Self {
+            Self { a: a + b as i32, b }
+        }
+
+        const DEFAULT_B: u8 = 2;
+
+        fn a(&self) -> i32 {
+            self.a
+        }
+
+        fn without_optionals(a: i32) -> Self {
+            Self { a, b: 0 }
+        }
+    }
+}
+
+#[test]
+fn associated_function() {
+    assert_eq!(Foo_new!(3), Foo { a: 3, b: 1 });
+    assert_eq!(Foo_new!(3, b = 5), Foo { a: 3, b: 5 });
+    assert_eq!(Foo_with_sum!(b = 1, a = 1), Foo { a: 2, b: 1 });
+    assert_eq!(Foo_with_sum!(), Foo { a: 2, b: 2 });
+    assert_eq!(Foo::new(4, 0).a(), 4);
+    assert_eq!(Foo::without_optionals(1), Foo { a: 1, b: 0 });
+}
+
+#[derive(Debug, PartialEq)]
+struct Wrapper<T> {
+    value: T,
+    count: usize,
+}
+
+opt_args! {
+    impl<T: Default> Wrapper<T> {
+        #[opt_args(non_export, rename = wrap)]
+        fn new(value: T = T::default(), count: usize = 1) -> Self {
+            Self { value, count }
+        }
+    }
+}
+
+#[test]
+fn generic_impl() {
+    let result: Wrapper<u8> = wrap!();
+    assert_eq!(result, Wrapper { value: 0, count: 1 });
+    assert_eq!(
+        wrap!(value = "a", count = 2),
+        Wrapper {
+            value: "a",
+            count: 2
+        }
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Counter {
+    count: u8,
+}
+
+opt_args! {
+    impl Counter {
+        #[opt_args(non_export)]
+        fn consume(mut self: Box<Self>, a: u8 = 1) -> Box<Self> {
+            self.count += a;
+            self
+        }
+
+        #[opt_args(non_export)]
+        fn shared(self: Rc<Self>, a: u8 = 1, b: u8 = Self::STEP) -> (usize, u8) {
+            (Rc::strong_count(&self), self.count + a + b)
+        }
+
+        #[opt_args(non_export)]
+        fn get(&self, a: u8?) -> u8 {
+            self.count + a
+        }
+
+        #[opt_args(non_export)]
+        fn add(&mut self, a: u8 = 1) {
+            self.count += a;
+        }
+
+        const STEP: u8 = 10;
+    }
+}
+
+#[test]
+fn receivers() {
+    let boxed = Box::new(Counter { count: 1 });
+    let boxed = Counter_consume!(boxed);
+    assert_eq!(
+        Counter_consume!(boxed, a = 3),
+        Box::new(Counter { count: 5 })
+    );
+
+    let rc = Rc::new(Counter { count: 1 });
+    assert_eq!(Counter_shared!(rc.clone()), (2, 12));
+    assert_eq!(Counter_shared!(rc, b = 0), (1, 2));
+
+    let mut counter = Counter { count: 1 };
+    Counter_add!(counter);
+    Counter_add!(counter, a = 2);
+    assert_eq!(Counter_get!(counter), 4);
+    assert_eq!(Counter_get!(counter, a = 1), 5);
+    assert_eq!(counter, Counter { count: 4 });
+}
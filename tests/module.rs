@@ -0,0 +1,193 @@
+use opt_args::opt_args;
+
+opt_args! {
+    mod shapes {
+        use std::fmt::Debug;
+
+        #[opt_args(non_export)]
+        pub fn describe<T: Debug>(value: T, prefix: &str = "value") -> String {
+            format!("{}: {:?}", prefix, value)
+        }
+
+        #[opt_args(non_export, shuffle)]
+        #[derive(Debug, PartialEq)]
+        pub struct Rect {
+            pub width: u32 = 1,
+            pub height: u32 = width,
+        }
+
+        pub fn area(rect: &Rect) -> u32 {
+            rect.width * rect.height
+        }
+
+        pub mod nested {
+            #[opt_args(non_export)]
+            pub fn double(a: u32 = 1) -> u32 {
+                a * 2
+            }
+        }
+
+        pub struct Unit;
+
+        impl Rect {
+            #[opt_args(non_export)]
+            pub fn square(side: u32 = 2) -> Self {
+                Self {
+                    width: side,
+                    height: side,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn module() {
+    assert_eq!(describe!(1), "value: 1");
+    assert_eq!(describe!("a", prefix = "b"), "b: \"a\"");
+    assert_eq!(
+        Rect!(height = 3, width = 2),
+        shapes::Rect {
+            width: 2,
+            height: 3
+        }
+    );
+    assert_eq!(shapes::area(&Rect!(width = 4)), 16);
+    assert_eq!(double!(), 2);
+    assert_eq!(Rect_square!(), shapes::Rect::square(2));
+    let _ = shapes::Unit;
+}
+
+mod shared {
+    use opt_args::opt_args;
+
+    opt_args! {
+        #[opt_args(crate_export, item_path = crate::shared)]
+        pub fn scale(a: u32, factor: u32 = 10) -> u32 {
+            a * factor
+        }
+    }
+
+    pub mod nested {
+        use opt_args::opt_args;
+
+        opt_args! {
+            #[opt_args(crate_export, shuffle, item_path = crate::shared::nested)]
+            pub(crate) fn offset(a: i32, by: i32 = 1, times: i32 = 1) -> i32 {
+                a + by * times
+            }
+        }
+    }
+}
+
+opt_args! {
+    #[opt_args(crate_export)]
+    fn negate(a: i32, negate: bool = true) -> i32 {
+        if negate { -a } else { a }
+    }
+}
+
+mod by_path {
+    use crate::negate;
+    use crate::shared::{nested::offset, scale};
+
+    pub fn call() -> (u32, i32, i32) {
+        (
+            scale!(2, factor = 3),
+            offset!(1, times = 3, by = 2),
+            negate!(4),
+        )
+    }
+}
+
+#[test]
+fn crate_export() {
+    assert_eq!(by_path::call(), (6, 7, -4));
+    assert_eq!(shared::scale!(2), 20);
+}
+
+mod padding {
+    use opt_args::opt_args;
+
+    opt_args! {
+        #[opt_args(crate_export)]
+        pub fn padded<const N: usize>(pad: [u8; N] = [0; N], fill: usize = N) -> ([u8; N], usize) {
+            (pad, fill)
+        }
+    }
+}
+
+mod deferred {
+    use crate::padding::padded;
+
+    pub fn call() -> (([u8; 2], usize), ([u8; 3], usize)) {
+        (padded!(), padded!(pad = [1, 2, 3]))
+    }
+}
+
+#[test]
+fn imported_helper() {
+    assert_eq!(deferred::call(), (([0, 0], 2), ([1, 2, 3], 3)));
+}
+
+mod ids {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use opt_args::opt_args;
+
+    pub static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    fn next_id() -> usize {
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    }
+
+    opt_args! {
+        #[opt_args(crate_export)]
+        pub fn tagged(name: &str, #[opt_args(cached)] id: usize = next_id()) -> String {
+            format!("{}#{}", name, id)
+        }
+    }
+}
+
+mod cached {
+    use crate::ids::tagged;
+
+    pub fn call() -> [String; 3] {
+        [tagged!("a"), tagged!("b"), tagged!("c", id = 7)]
+    }
+}
+
+#[test]
+fn imported_cached_default() {
+    use std::sync::atomic::Ordering;
+
+    assert_eq!(cached::call(), ["a#0", "b#0", "c#7"]);
+    assert_eq!(ids::NEXT.load(Ordering::SeqCst), 1);
+}
+
+mod lookup {
+    use std::collections::HashMap;
+
+    use opt_args::opt_args;
+
+    opt_args! {
+        #[opt_args(crate_export, transparent_errors)]
+        pub fn get(map: HashMap<u8, u8>, key: u8?) -> Option<u8> {
+            map.get(&key).copied()
+        }
+    }
+}
+
+mod transparent {
+    use crate::lookup::get;
+
+    pub fn call() -> (Option<u8>, Option<u8>) {
+        let map = std::collections::HashMap::from([(0, 1), (2, 3)]);
+        (get!(map.clone()), get!(map, key = 2))
+    }
+}
+
+#[test]
+fn transparent_errors_imported_type() {
+    assert_eq!(transparent::call(), (Some(1), Some(3)));
+}
@@ -0,0 +1,57 @@
+use opt_args::opt_args;
+
+pub struct Registry {
+    pub name: &'static str,
+    pub module: &'static str,
+    pub optional: &'static [&'static str],
+}
+
+inventory::collect!(Registry);
+
+opt_args! {
+    #[opt_args(register = Registry, non_export)]
+    fn f(a: u8, b: u8 = 5, c: u8?) -> u8 {
+        a + b + c
+    }
+
+    #[opt_args(register = Registry, non_export)]
+    enum E {
+        A { a: u8, b: u8? },
+        B { c: u8 = 1 },
+    }
+}
+
+struct S;
+
+opt_args! {
+    impl S {
+        #[opt_args(register = Registry, non_export)]
+        fn new(a: u8?) -> Self {
+            let _ = a;
+            Self
+        }
+    }
+}
+
+#[test]
+fn registered_items() {
+    let mut items: Vec<_> = inventory::iter::<Registry>
+        .into_iter()
+        .map(|item| (item.name, item.module, item.optional))
+        .collect();
+    items.sort();
+    assert_eq!(
+        items,
+        [
+            ("E::A", "registry", &["b"][..]),
+            ("E::B", "registry", &["c"]),
+            ("S::new", "registry", &["a"]),
+            ("f", "registry", &["b", "c"]),
+        ]
+    );
+    // the macros are still generated
+    assert_eq!(f!(1), 6);
+    assert!(matches!(E!(B), E::B { c: 1 }));
+    assert!(matches!(E!(A, 1), E::A { a: 1, b: 0 }));
+    let S = S_new!();
+}
@@ -54,3 +54,749 @@ fn opt_struct() {
         }
     );
 }
+
+#[test]
+fn phantom_data() {
+    use std::marker::PhantomData;
+
+    opt_args! {
+        #[opt_args(shuffle, non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Phantom<T> {
+            a: i32,
+            b: i32?,
+            _marker: PhantomData<T>?,
+            _other_marker: std::marker::PhantomData<(T, T)> = PhantomData,
+        }
+    }
+
+    let result: Phantom<String> = Phantom!(1);
+    assert_eq!(
+        result,
+        Phantom {
+            a: 1,
+            b: 0,
+            _marker: PhantomData,
+            _other_marker: PhantomData
+        }
+    );
+    let result: Phantom<u8> = Phantom!(1, b = 2);
+    assert_eq!(
+        result,
+        Phantom {
+            a: 1,
+            b: 2,
+            _marker: PhantomData,
+            _other_marker: PhantomData
+        }
+    );
+}
+
+#[test]
+fn assert_defaults() {
+    opt_args! {
+        #[opt_args(assert_defaults, non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Asserted<'a, T> {
+            a: i32?,
+            b: &'a str?,
+            c: Vec<T>?,
+            d: (u8, String)?,
+        }
+    }
+
+    let result: Asserted<'_, u8> = Asserted!(d = (1, String::from("d")));
+    assert_eq!(
+        result,
+        Asserted {
+            a: 0,
+            b: "",
+            c: vec![],
+            d: (1, String::from("d")),
+        }
+    );
+}
+
+#[test]
+fn deferred_defaults() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Buffer<const N: usize> {
+            name: &'static str,
+            len: usize = N,
+            data: [u8; N] = [1; N],
+            tag: u8 = Self::TAG,
+        }
+    }
+
+    impl<const N: usize> Buffer<N> {
+        const TAG: u8 = N as u8 * 2;
+    }
+
+    let result: Buffer<3> = Buffer!("a");
+    assert_eq!(
+        result,
+        Buffer {
+            name: "a",
+            len: 3,
+            data: [1; 3],
+            tag: 6
+        }
+    );
+    assert_eq!(
+        Buffer!("b", data = [5, 5], tag = 0),
+        Buffer {
+            name: "b",
+            len: 2,
+            data: [5; 2],
+            tag: 0
+        }
+    );
+}
+
+#[test]
+fn positional() {
+    opt_args! {
+        #[opt_args(positional, non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Color {
+            r: u8?,
+            g: u8?,
+            b: u8 = 255,
+        }
+    }
+
+    assert_eq!(Color!(), Color { r: 0, g: 0, b: 255 });
+    assert_eq!(
+        Color!(255),
+        Color {
+            r: 255,
+            g: 0,
+            b: 255
+        }
+    );
+    assert_eq!(
+        Color!(255, 0),
+        Color {
+            r: 255,
+            g: 0,
+            b: 255
+        }
+    );
+    assert_eq!(Color!(1, 2, 3), Color { r: 1, g: 2, b: 3 });
+    assert_eq!(Color!(.., 2), Color { r: 0, g: 2, b: 255 });
+    assert_eq!(Color!(1, b = 3), Color { r: 1, g: 0, b: 3 });
+}
+
+#[test]
+fn nested_generics() {
+    use std::{borrow::Cow, collections::HashMap};
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Chunks<'a, 'b> {
+            name: &'a str,
+            data: HashMap<&'a str, Vec<Cow<'b, [u8]>>> = HashMap::new(),
+        }
+    }
+
+    assert_eq!(
+        Chunks!("empty"),
+        Chunks {
+            name: "empty",
+            data: HashMap::new()
+        }
+    );
+    let bytes = [1, 2, 3];
+    let data = HashMap::from([("a", vec![Cow::Borrowed(&bytes[..]), Cow::Owned(vec![4])])]);
+    assert_eq!(
+        Chunks!("full", data = data.clone()),
+        Chunks { name: "full", data }
+    );
+}
+
+#[test]
+fn block_default() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Point {
+            x: i32 = {
+                let offset = 10;
+                offset * 2
+            },
+            y: i32?,
+        }
+    }
+
+    assert_eq!(Point!(), Point { x: 20, y: 0 });
+    assert_eq!(
+        Point!(
+            y = {
+                let one = 1;
+                one + one
+            }
+        ),
+        Point { x: 20, y: 2 }
+    );
+}
+
+// enums are not supported by `opt_args!` yet, but enum-valued defaults work on struct fields
+#[test]
+fn enum_default() {
+    mod palette {
+        #[derive(Debug, PartialEq)]
+        pub enum Color {
+            Black,
+            Rgb(u8, u8, u8),
+        }
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Pen {
+            width: u8,
+            color: palette::Color = palette::Color::Black,
+        }
+    }
+
+    assert!(matches!(
+        Pen!(1),
+        Pen {
+            width: 1,
+            color: palette::Color::Black
+        }
+    ));
+    assert_eq!(
+        Pen!(2, color = palette::Color::Rgb(255, 0, 0)),
+        Pen {
+            width: 2,
+            color: palette::Color::Rgb(255, 0, 0)
+        }
+    );
+}
+
+#[test]
+fn cow_default() {
+    use std::borrow::Cow;
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct User<'a> {
+            id: u32,
+            name: Cow<'a, str> = Cow::Borrowed("default"),
+        }
+    }
+
+    let user = User!(1);
+    assert!(matches!(user.name, Cow::Borrowed("default")));
+    let name = String::from("owned");
+    let user = User!(2, name = Cow::Owned(name.clone()));
+    assert_eq!(
+        user,
+        User {
+            id: 2,
+            name: Cow::Owned(name)
+        }
+    );
+    let borrowed = String::from("borrowed");
+    assert_eq!(User!(3, name = Cow::Borrowed(&borrowed)).name, "borrowed");
+}
+
+#[test]
+fn macro_case() {
+    opt_args! {
+        #[opt_args(macro_case = snake, non_export)]
+        #[derive(Debug, PartialEq)]
+        struct PointXY {
+            x: i32?,
+            y: i32?,
+        }
+
+        #[opt_args(macro_case = pascal, non_export)]
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, PartialEq)]
+        struct raw_point {
+            x: i32 = 1,
+        }
+
+        #[opt_args(macro_case = keep, non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Kept {
+            x: i32 = 2,
+        }
+    }
+
+    assert_eq!(point_xy!(x = 1), PointXY { x: 1, y: 0 });
+    assert_eq!(RawPoint!(), raw_point { x: 1 });
+    assert_eq!(Kept!(), Kept { x: 2 });
+}
+
+#[test]
+fn path_default() {
+    #[derive(Debug, PartialEq)]
+    enum State {
+        Idle,
+        Running(u8),
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Marker;
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Machine {
+            state: State = State::Idle,
+            marker: Marker = Marker,
+        }
+    }
+
+    assert_eq!(
+        Machine!(),
+        Machine {
+            state: State::Idle,
+            marker: Marker
+        }
+    );
+    assert_eq!(Machine!(state = State::Running(1)).state, State::Running(1));
+}
+
+#[test]
+fn sibling_defaults() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Pair<T: Clone> {
+            first: T,
+            second: T = first.clone(),
+        }
+    }
+
+    assert_eq!(
+        Pair!(5u8),
+        Pair {
+            first: 5,
+            second: 5
+        }
+    );
+    assert_eq!(
+        Pair!(5u8, second = 6),
+        Pair {
+            first: 5,
+            second: 6
+        }
+    );
+    let pair = Pair!(String::from("a"));
+    assert_eq!(pair.second, "a");
+}
+
+#[test]
+fn array_repeat_default() {
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    impl Point {
+        const ORIGIN: Point = Point { x: 0, y: 0 };
+    }
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Triangle {
+            points: [Point; 3] = [Point::ORIGIN; 3],
+            name: &'static str?,
+        }
+    }
+
+    assert_eq!(
+        Triangle!(),
+        Triangle {
+            points: [Point::ORIGIN; 3],
+            name: ""
+        }
+    );
+    let points = [Point { x: 1, y: 2 }, Point::ORIGIN, Point { x: 3, y: 4 }];
+    assert_eq!(
+        Triangle!(points = points, name = "t"),
+        Triangle { points, name: "t" }
+    );
+}
+
+#[test]
+fn test_helpers() {
+    // generates the values 1, 2, 3, ...
+    struct Sequence(u8);
+
+    impl Sequence {
+        fn r#gen<T: From<u8>>(&mut self) -> T {
+            self.0 += 1;
+            T::from(self.0)
+        }
+    }
+
+    opt_args! {
+        #[opt_args(test_helpers, shuffle, non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Foo {
+            a: u8,
+            b: u16 = 5,
+            c: u32?,
+        }
+    }
+
+    let mut rng = Sequence(0);
+    assert_eq!(Foo_test!(rng, 1), Foo { a: 1, b: 1, c: 2 });
+    assert_eq!(Foo_test!(rng, 1, c = 0), Foo { a: 1, b: 3, c: 0 });
+    assert_eq!(Foo_test!(rng, 1, c = 0, b = 0), Foo { a: 1, b: 0, c: 0 });
+    assert_eq!(Foo!(1), Foo { a: 1, b: 5, c: 0 });
+}
+
+#[test]
+fn const_generics_defaults() {
+    opt_args! {
+        #[opt_args(non_export, const_generics_defaults(N = 3))]
+        #[derive(Debug, PartialEq)]
+        struct Buffer<T: Default + Copy, const N: usize> {
+            data: [T; N] = [Default::default(); N],
+            len: usize?,
+        }
+    }
+
+    let buffer: Buffer<u8, 3> = Buffer!();
+    assert_eq!(
+        buffer,
+        Buffer {
+            data: [0; 3],
+            len: 0
+        }
+    );
+    assert_eq!(
+        Buffer!(len = 1, N = 2),
+        Buffer {
+            data: [0u8; 2],
+            len: 1
+        }
+    );
+}
+
+#[test]
+fn builder() {
+    opt_args! {
+        #[opt_args(builder)]
+        #[derive(Debug, PartialEq)]
+        struct Request<'a, T> {
+            url: &'a str,
+            body: T,
+            retries: u8 = 3,
+            timeout: u32 = retries as u32 * 10,
+            verbose: bool?,
+        }
+    }
+
+    let request = Request::builder("url", ()).with_verbose(true).build();
+    assert_eq!(
+        request,
+        Request {
+            url: "url",
+            body: (),
+            retries: 3,
+            timeout: 30,
+            verbose: true,
+        }
+    );
+    let request = Request::builder("url", 1).with_retries(1).build();
+    assert_eq!(
+        request,
+        Request {
+            url: "url",
+            body: 1,
+            retries: 1,
+            timeout: 10,
+            verbose: false,
+        }
+    );
+}
+
+#[test]
+fn skip() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct S {
+            a: u8 = 1,
+            #[opt_args(skip)]
+            b: u8 = 2,
+            c: u8?,
+        }
+    }
+
+    assert_eq!(S!(5), S { a: 1, b: 5, c: 0 });
+    assert_eq!(S!(5, c = 3), S { a: 1, b: 5, c: 3 });
+}
+
+#[test]
+fn tuple_struct() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Color(u8, u8, pub u8 = 255, u8?);
+    }
+
+    assert_eq!(Color!(10, 20), Color(10, 20, 255, 0));
+    assert_eq!(Color!(10, 20, 30), Color(10, 20, 30, 0));
+    assert_eq!(Color!(10, 20, 30, 40), Color(10, 20, 30, 40));
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Wrapper<T: Default>(T, Vec<T> = Vec::new())
+        where
+            T: Clone;
+    }
+
+    assert_eq!(Wrapper!(1), Wrapper(1, vec![]));
+    assert_eq!(Wrapper!(1, vec![2]), Wrapper(1, vec![2]));
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Pair<T: From<u8>>(T, T = T::from(7));
+    }
+
+    assert_eq!(Pair!(1u32), Pair(1, 7));
+}
+
+#[test]
+fn struct_update() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq, Clone)]
+        struct Config {
+            name: String,
+            retries: u8 = 3,
+            verbose: bool?,
+        }
+    }
+
+    let base = Config!("base".to_string(), retries = 5);
+    assert_eq!(
+        Config!(verbose = true, ..base.clone()),
+        Config {
+            name: "base".to_string(),
+            retries: 5,
+            verbose: true,
+        }
+    );
+    assert_eq!(
+        Config!(name = "other".to_string(), ..base.clone()),
+        Config {
+            name: "other".to_string(),
+            retries: 5,
+            verbose: false,
+        }
+    );
+}
+
+#[test]
+fn range_field() {
+    use std::ops::RangeTo;
+
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq, Clone)]
+        struct R {
+            r: RangeTo<i32>,
+            b: i32?,
+        }
+    }
+
+    // a range passed by position isn't mistaken for the base of the struct update syntax
+    assert_eq!(R!(..5), R { r: ..5, b: 0 });
+    assert_eq!(R!(..5, b = 1), R { r: ..5, b: 1 });
+    assert_eq!(R!(r = ..3, ..R!(..5, b = 1)), R { r: ..3, b: 1 });
+}
+
+#[test]
+fn const_generic_param() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Matrix<const R: usize, const C: usize> {
+            rows: [[i32; C]; R],
+            scale: i32 = 1,
+        }
+    }
+
+    assert_eq!(
+        Matrix!([[1, 2, 3]; 2]),
+        Matrix {
+            rows: [[1, 2, 3]; 2],
+            scale: 1
+        }
+    );
+    assert_eq!(Matrix!([[0; 1]; 1], scale = 3).scale, 3);
+}
+
+#[test]
+fn snapshot_arms() {
+    opt_args! {
+        #[opt_args(non_export, snapshot_arms)]
+        struct Color(u8, u8 = 0);
+    }
+
+    assert_eq!(COLOR_ARMS, "Color!(_0)\nColor!(_0, _1)");
+    let color = Color!(1);
+    assert_eq!((color.0, color.1), (1, 0));
+}
+
+#[test]
+fn where_clause() {
+    opt_args! {
+        #[opt_args(non_export)]
+        #[derive(Debug, PartialEq)]
+        struct Holder<T>
+        where
+            T: Clone + Default,
+        {
+            value: T,
+            fallback: T = T::default(),
+        }
+    }
+
+    assert_eq!(
+        Holder!(1u8),
+        Holder {
+            value: 1,
+            fallback: 0
+        }
+    );
+    assert_eq!(
+        Holder!(1u8, fallback = 2),
+        Holder {
+            value: 1,
+            fallback: 2
+        }
+    );
+}
+
+opt_args! {
+    #[opt_args(non_export, validate_at_build = check_range)]
+    #[derive(Debug, PartialEq)]
+    struct Range {
+        start: u32 = 0,
+        end: u32 = 10,
+    }
+}
+
+fn check_range(range: Range) -> Result<Range, String> {
+    if range.start <= range.end {
+        Ok(range)
+    } else {
+        Err(format!("{} is after {}", range.start, range.end))
+    }
+}
+
+#[test]
+fn validate_at_build() {
+    assert_eq!(Range!(), Ok(Range { start: 0, end: 10 }));
+    assert_eq!(Range!(start = 5), Ok(Range { start: 5, end: 10 }));
+    assert_eq!(Range!(start = 11), Err("11 is after 10".to_string()));
+    assert_eq!(
+        Range!(end = 3, ..Range { start: 4, end: 5 }),
+        Err("4 is after 3".to_string())
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Handwritten {
+    x: i32,
+    y: i32,
+}
+
+opt_args! {
+    #[opt_args(non_export)]
+    @existing struct Handwritten {
+        x: i32,
+        y: i32 = 1,
+    }
+}
+
+#[test]
+fn existing() {
+    assert_eq!(Handwritten!(4), Handwritten { x: 4, y: 1 });
+    assert_eq!(Handwritten!(4, y = 2), Handwritten { x: 4, y: 2 });
+}
+
+#[deny(unused_must_use)]
+#[test]
+fn serde_defaults() {
+    use serde::Deserialize;
+
+    opt_args! {
+        #[opt_args(serde_defaults, non_export)]
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Config {
+            name: String,
+            retries: u8 = 3,
+            verbose: bool?,
+            tags: Vec<String> = vec!["default".to_string()],
+        }
+    }
+
+    let config: Config = serde_json::from_str(r#"{"name": "a"}"#).unwrap();
+    assert_eq!(config, Config!("a".to_string()));
+    let config: Config = serde_json::from_str(r#"{"name": "b", "verbose": true}"#).unwrap();
+    assert_eq!(config, Config!("b".to_string(), verbose = true));
+    let config: Config =
+        serde_json::from_str(r#"{"name": "c", "retries": 1, "tags": []}"#).unwrap();
+    assert_eq!(config, Config!("c".to_string(), retries = 1, tags = vec![]));
+}
+
+mod must_use {
+    use opt_args::opt_args;
+
+    opt_args! {
+        #[opt_args(non_export, must_use)]
+        #[derive(Debug, PartialEq)]
+        pub struct Size {
+            width: u32,
+            height: u32 = 1,
+        }
+    }
+
+    const UNIT: Size = Size!(1);
+
+    #[test]
+    fn must_use() {
+        assert_eq!(
+            UNIT,
+            Size {
+                width: 1,
+                height: 1
+            }
+        );
+        let size = Size!(2, height = 3);
+        assert_eq!(
+            size,
+            Size {
+                width: 2,
+                height: 3
+            }
+        );
+        assert_eq!(
+            Size!(height = 2, ..UNIT),
+            Size {
+                width: 1,
+                height: 2
+            }
+        );
+    }
+}